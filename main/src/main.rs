@@ -1,7 +1,6 @@
 use mdds;
 
-// Just a thin shim that awaits a `run()` function in mdds `lib.rs`
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    mdds::run().await
+// Just a thin shim that calls a `run()` function in mdds `lib.rs`, which builds the configured tokio runtime
+fn main() -> anyhow::Result<()> {
+    mdds::run()
 }
\ No newline at end of file
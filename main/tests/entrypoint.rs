@@ -0,0 +1,40 @@
+//! Runs the `main` binary to confirm it starts the server by `mdds::run`, i.e. by `http::serve`.
+
+use std::process::Command;
+
+fn main_command() -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_main"));
+    // Only the variables of a test are configured, not those of the environment running it
+    for (name, _) in std::env::vars().filter(|(name, _)| name.starts_with("MDDS_")) {
+        command.env_remove(name);
+    }
+    command
+}
+
+#[test]
+fn parses_the_config_of_mdds() {
+    let output = main_command().arg("--version").output().unwrap();
+
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("mdds "));
+}
+
+#[test]
+fn serves_by_http_serve() {
+    // Only `http::serve` checks the market data path, before binding any address
+    let market_data_path = std::env::temp_dir().join("mdds-entrypoint-test-missing");
+    let output = main_command()
+        .env("MDDS_MARKET_DATA_PATH", &market_data_path)
+        .env("MDDS_SERVER_ADDRESS", "127.0.0.1:0")
+        .env("MDDS_METRICS_ADDRESS", "127.0.0.1:0")
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains(&format!("MDDS_MARKET_DATA_PATH {} is not readable", market_data_path.display())),
+        "{}",
+        stderr,
+    );
+}
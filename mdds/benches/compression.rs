@@ -0,0 +1,45 @@
+//! Requests a day of `ethusdt.trade` as JSON by each `Accept-Encoding` the compression layer supports.
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use axum::body::Body;
+use axum::http::header::ACCEPT_ENCODING;
+use axum::http::Request;
+use axum::Router;
+use common::{trades_of_day, MarketData};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use http_body_util::BodyExt;
+use tower::ServiceExt;
+
+/// About the trades of a quiet day of `ethusdt`.
+const TRADES_PER_DAY: usize = 200_000;
+
+const DAY_URI: &str = "/api/v1/market-data/binance/spot/trade/ethusdt?from=2019-04-05T00:00:00Z&to=2019-04-06T00:00:00Z";
+
+/// Returns the size of the response body as sent, i.e. compressed by the encoding.
+async fn body_size(router: &Router, encoding: &str) -> usize {
+    let request = Request::get(DAY_URI).header(ACCEPT_ENCODING, encoding).body(Body::empty()).unwrap();
+    let response = router.clone().oneshot(request).await.unwrap();
+    assert!(response.status().is_success());
+    response.into_body().collect().await.unwrap().to_bytes().len()
+}
+
+fn compression(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let market_data = MarketData::new();
+    market_data.write_day("trade", "ethusdt", "2019-04-05", &trades_of_day(5, TRADES_PER_DAY));
+    let router = market_data.router(&["--max-response-messages", "0"]);
+
+    let mut group = c.benchmark_group("compression");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(TRADES_PER_DAY as u64));
+    for encoding in ["identity", "gzip", "zstd"] {
+        println!("{}: {} bytes", encoding, runtime.block_on(body_size(&router, encoding)));
+        group.bench_function(encoding, |b| b.to_async(&runtime).iter(|| body_size(&router, encoding)));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, compression);
+criterion_main!(benches);
@@ -0,0 +1,50 @@
+//! Reads a day of `ethusdt.trade` serially and prefetched by `parquet_prefetch_batches`, by the batch
+//! endpoint, which reads whole files, and the streaming endpoint, which sends the entries as they are read.
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use axum::body::Body;
+use axum::http::Request;
+use axum::Router;
+use common::{trades_of_day, MarketData};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use http_body_util::BodyExt;
+use tower::ServiceExt;
+
+const TRADES_PER_DAY: usize = 200_000;
+
+const DAY_QUERY: &str = "binance/spot/trade/ethusdt?from=2019-04-05T00:00:00Z&to=2019-04-06T00:00:00Z";
+
+async fn read_day(router: &Router, uri: &str) -> usize {
+    let request = Request::get(uri).body(Body::empty()).unwrap();
+    let response = router.clone().oneshot(request).await.unwrap();
+    assert!(response.status().is_success());
+    response.into_body().collect().await.unwrap().to_bytes().len()
+}
+
+fn prefetch(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let market_data = MarketData::new();
+    market_data.write_day("trade", "ethusdt", "2019-04-05", &trades_of_day(5, TRADES_PER_DAY));
+
+    let mut group = c.benchmark_group("prefetch");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(TRADES_PER_DAY as u64));
+    // `0` reads serially, each batch only once the previous one is decoded
+    for prefetch_batches in [0, 2, 8] {
+        let router = market_data.router(&[
+            "--max-response-messages", "0",
+            "--parquet-prefetch-batches", &prefetch_batches.to_string(),
+        ]);
+        for (endpoint, uri) in [("batch", format!("/api/v1/market-data/{}", DAY_QUERY)), ("stream", format!("/stream/v1/market-data/{}", DAY_QUERY))] {
+            group.bench_with_input(BenchmarkId::new(endpoint, prefetch_batches), &uri, |b, uri| {
+                b.to_async(&runtime).iter(|| read_day(&router, uri))
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, prefetch);
+criterion_main!(benches);
@@ -0,0 +1,19 @@
+use std::process::Command;
+
+fn main() {
+    // Embeds the commit the server is built from, if built from a git checkout with git installed
+    let commit = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok());
+    if let Some(commit) = commit {
+        println!("cargo:rustc-env=MDDS_GIT_COMMIT={}", commit.trim());
+    }
+    println!("cargo:rerun-if-changed=../.git/HEAD");
+    // A commit on the checked out branch only updates the branch's ref, not HEAD itself
+    if let Some(head_ref) = std::fs::read_to_string("../.git/HEAD").ok().as_deref().and_then(|head| head.strip_prefix("ref: ")) {
+        println!("cargo:rerun-if-changed=../.git/{}", head_ref.trim());
+    }
+}
@@ -0,0 +1,161 @@
+//! A typed HTTP client of the market data routes, for services consuming the data served by mdds.
+
+use crate::codec::Message;
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures::{stream, Stream, StreamExt, TryStreamExt};
+use serde::Deserialize;
+
+/// The errors of [`MddsClient`] requests.
+#[derive(thiserror::Error, Debug)]
+pub enum ClientError {
+    #[error("Error sending request: {0}")]
+    Http(#[from] reqwest::Error),
+    /// The server answered with an error status and the `code` and `message` of its error body.
+    #[error("Server responded with {status} {code}: {message}")]
+    Api {
+        status: reqwest::StatusCode,
+        code: String,
+        message: String,
+    },
+    #[error("Error decoding message: {0}")]
+    Decode(#[from] serde_json::Error),
+}
+
+#[derive(Deserialize)]
+struct ApiResponse {
+    messages: Vec<Message>,
+    next_cursor: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: ErrorBody,
+}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    code: String,
+    message: String,
+}
+
+/// An async client of an mdds server, e.g. `MddsClient::new("http://localhost:8080")`.
+#[derive(Clone, Debug)]
+pub struct MddsClient {
+    http: reqwest::Client,
+    base_url: String,
+    api_key: Option<String>,
+}
+
+impl MddsClient {
+
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self::with_http_client(reqwest::Client::new(), base_url)
+    }
+
+    /// Uses a preconfigured `reqwest` client, e.g. with timeouts or proxies.
+    pub fn with_http_client(http: reqwest::Client, base_url: impl Into<String>) -> Self {
+        let base_url = base_url.into().trim_end_matches('/').to_string();
+        Self { http, base_url, api_key: None }
+    }
+
+    /// Sends the key as `Authorization: Bearer <key>` with every request.
+    pub fn with_api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    /// Returns all messages of the time range, following the `next_cursor` of the pages.
+    pub async fn get_market_data(
+        &self,
+        exchange: &str,
+        market_type: &str,
+        stream: &str,
+        symbol: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<Message>, ClientError> {
+        let url = format!("{}/api/v1/market-data/{}/{}/{}/{}", self.base_url, exchange, market_type, stream, symbol);
+        let mut messages = Vec::new();
+        let mut cursor = None;
+
+        loop {
+            let mut query = time_range_query(from, to);
+            if let Some(cursor) = cursor {
+                query.push(("cursor", cursor.to_string()));
+            }
+            let response = self.send(self.request(&url).query(&query)).await?;
+            let page: ApiResponse = response.json().await?;
+
+            messages.extend(page.messages);
+            match page.next_cursor {
+                Some(next_cursor) => cursor = Some(next_cursor),
+                None => return Ok(messages),
+            }
+        }
+    }
+
+    /// Streams the messages of the time range as they are read, from the newline-delimited JSON of the stream route.
+    pub async fn stream_market_data(
+        &self,
+        exchange: &str,
+        market_type: &str,
+        stream: &str,
+        symbol: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<impl Stream<Item = Result<Message, ClientError>>, ClientError> {
+        let url = format!("{}/stream/v1/market-data/{}/{}/{}/{}", self.base_url, exchange, market_type, stream, symbol);
+        let response = self.send(self.request(&url).query(&time_range_query(from, to))).await?;
+
+        let chunks = response.bytes_stream().map_err(ClientError::from);
+        let lines = stream::try_unfold((chunks.boxed(), Vec::new()), |(mut chunks, mut buffer)| async move {
+            loop {
+                if let Some(end) = buffer.iter().position(|byte| *byte == b'\n') {
+                    let line: Vec<u8> = buffer.drain(..=end).collect();
+                    return Ok(Some((line, (chunks, buffer))));
+                }
+                match chunks.next().await {
+                    Some(chunk) => buffer.extend_from_slice(&chunk?),
+                    // The last line may come without a trailing newline
+                    None if buffer.is_empty() => return Ok(None),
+                    None => return Ok(Some((std::mem::take(&mut buffer), (chunks, buffer)))),
+                }
+            }
+        });
+
+        Ok(lines
+            .try_filter(|line| futures::future::ready(!line.trim_ascii().is_empty()))
+            .and_then(|line| async move { Ok(serde_json::from_slice::<Message>(&line)?) }))
+    }
+
+    fn request(&self, url: &str) -> reqwest::RequestBuilder {
+        let request = self.http.get(url);
+        match &self.api_key {
+            Some(api_key) => request.bearer_auth(api_key),
+            None => request,
+        }
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<reqwest::Response, ClientError> {
+        let response = request.send().await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+
+        // Fall back to the status for errors without a JSON body, e.g. of a proxy
+        let body = response.text().await?;
+        let (code, message) = match serde_json::from_str::<ErrorResponse>(&body) {
+            Ok(error) => (error.error.code, error.error.message),
+            Err(_) => (status.as_str().to_string(), body),
+        };
+        Err(ClientError::Api { status, code, message })
+    }
+}
+
+fn time_range_query(from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<(&'static str, String)> {
+    vec![
+        ("from", from.to_rfc3339_opts(SecondsFormat::Millis, true)),
+        ("to", to.to_rfc3339_opts(SecondsFormat::Millis, true)),
+    ]
+}
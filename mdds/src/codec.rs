@@ -0,0 +1,154 @@
+//! The messages returned to clients and their conversion from the entries of the parquet files.
+
+// TODO: Move this to a separate codec repo to share with adapters and s9_parquet
+use base64::prelude::{Engine, BASE64_STANDARD};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::string::FromUtf8Error;
+use utoipa::ToSchema;
+
+/// The data of an entry is not valid UTF-8 and no base64 fallback was requested.
+#[derive(thiserror::Error, Debug)]
+#[error("Error decoding message data: {0}")]
+pub struct DecodeError(#[from] FromUtf8Error);
+
+/// How the `data` of a message is encoded.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DataEncoding {
+    #[default]
+    Utf8,
+    /// The data isn't valid UTF-8, e.g. binary protobuf, and is passed as base64 of the raw bytes.
+    Base64,
+}
+
+impl DataEncoding {
+
+    pub fn is_utf8(&self) -> bool {
+        *self == DataEncoding::Utf8
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct Message {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exchange: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub market_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream: Option<String>,
+    pub symbol: String,
+    pub timestamp_millis: i64,
+    pub timestamp_sec: i64,
+    pub timestamp_sub_sec: i32,
+    pub data: String,
+    /// Only serialized for `base64`, messages without it carry UTF-8 data.
+    #[serde(default, skip_serializing_if = "DataEncoding::is_utf8")]
+    pub encoding: DataEncoding,
+    /// Additional parquet columns of the row, only present if requested with `columns`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub columns: Option<BTreeMap<String, serde_json::Value>>,
+}
+
+impl Message {
+
+    /// Attaches the exchange, market type and stream the message was requested from.
+    pub fn with_meta(mut self, exchange: &str, market_type: &str, stream: &str) -> Self {
+        self.exchange = Some(exchange.to_string());
+        self.market_type = Some(market_type.to_string());
+        self.stream = Some(stream.to_string());
+        self
+    }
+}
+
+/// Decodes the data of a parquet entry into a message of the given symbol.
+///
+/// Data that isn't valid UTF-8 is an error, unless `base64_fallback` passes it base64 encoded.
+pub fn entry_to_message(entry: s9_parquet::Entry, symbol: &str, base64_fallback: bool) -> Result<Message, DecodeError> {
+    let timestamp_info = entry.timestamp_info;
+    let (data, encoding) = match String::from_utf8(entry.data) {
+        Ok(data) => (data, DataEncoding::Utf8),
+        Err(err) if base64_fallback => (BASE64_STANDARD.encode(err.into_bytes()), DataEncoding::Base64),
+        Err(err) => return Err(err.into()),
+    };
+    Ok(Message {
+        exchange: None,
+        market_type: None,
+        stream: None,
+        symbol: symbol.to_string(),
+        timestamp_millis: timestamp_info.timestamp_millis,
+        timestamp_sec: timestamp_info.timestamp_sec,
+        timestamp_sub_sec: timestamp_info.timestamp_sub_sec,
+        data,
+        encoding,
+        columns: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use s9_parquet::{Entry, TimestampInfo};
+
+    fn entry(data: &[u8]) -> Entry {
+        Entry {
+            timestamp_info: TimestampInfo {
+                timestamp_millis: 1_554_422_400_123,
+                timestamp_sec: 1_554_422_400,
+                timestamp_sub_sec: 123_456_789,
+            },
+            data: data.to_vec(),
+        }
+    }
+
+    #[test]
+    fn converts_an_entry_of_utf8_data() {
+        let message = entry_to_message(entry(br#"{"p":"1.5"}"#), "ethusdt", false).unwrap();
+        assert_eq!(message.symbol, "ethusdt");
+        assert_eq!(message.timestamp_millis, 1_554_422_400_123);
+        assert_eq!(message.timestamp_sec, 1_554_422_400);
+        assert_eq!(message.timestamp_sub_sec, 123_456_789);
+        assert_eq!(message.data, r#"{"p":"1.5"}"#);
+        assert_eq!(message.encoding, DataEncoding::Utf8);
+        assert!(message.exchange.is_none() && message.market_type.is_none() && message.stream.is_none());
+        assert!(message.columns.is_none());
+    }
+
+    #[test]
+    fn rejects_data_that_is_not_utf8_without_base64_fallback() {
+        assert!(entry_to_message(entry(&[0x08, 0x96, 0x01, 0xff]), "ethusdt", false).is_err());
+    }
+
+    #[test]
+    fn encodes_data_that_is_not_utf8_as_base64_with_fallback() {
+        let message = entry_to_message(entry(&[0x08, 0x96, 0x01, 0xff]), "ethusdt", true).unwrap();
+        assert_eq!(message.data, "CJYB/w==");
+        assert_eq!(message.encoding, DataEncoding::Base64);
+
+        // Valid UTF-8 is passed as is regardless of the fallback
+        let message = entry_to_message(entry(b"trade"), "ethusdt", true).unwrap();
+        assert_eq!(message.data, "trade");
+        assert_eq!(message.encoding, DataEncoding::Utf8);
+    }
+
+    #[test]
+    fn serializes_the_encoding_and_meta_only_if_set() {
+        let message = entry_to_message(entry(b"trade"), "ethusdt", false).unwrap();
+        let json = serde_json::to_value(&message).unwrap();
+        assert_eq!(json, serde_json::json!({
+            "symbol": "ethusdt",
+            "timestamp_millis": 1_554_422_400_123_i64,
+            "timestamp_sec": 1_554_422_400,
+            "timestamp_sub_sec": 123_456_789,
+            "data": "trade",
+        }));
+
+        let message = entry_to_message(entry(&[0xff]), "ethusdt", true).unwrap().with_meta("binance", "spot", "trade");
+        let json = serde_json::to_value(&message).unwrap();
+        assert_eq!(json["encoding"], "base64");
+        assert_eq!(json["exchange"], "binance");
+        assert_eq!(json["market_type"], "spot");
+        assert_eq!(json["stream"], "trade");
+    }
+}
@@ -8,24 +8,302 @@
 #[derive(clap::Parser, Debug)]
 #[command(version, about, long_about = None)]
 pub struct Config {
-    // The address of the mdds http server to listen for client requests.
+    // The address of the mdds http server to listen for client requests, or `unix:/path/to.sock` for a Unix domain socket.
     #[clap(long, env = "MDDS_SERVER_ADDRESS", default_value = "0.0.0.0:8080" )]
     pub server_address: String,
 
+    /// Serve HTTP/2 besides HTTP/1.1 on TCP. Without TLS this is h2c with prior knowledge, which
+    /// browsers don't speak but e.g. gRPC-style clients and proxies do. HTTP/2 negotiated by ALPN requires TLS.
+    #[clap(long, env = "MDDS_HTTP2_ENABLED", default_value_t = false, action = clap::ArgAction::Set)]
+    pub http2_enabled: bool,
+
+    /// The maximum number of concurrent requests of a single HTTP/2 connection.
+    #[clap(long, env = "MDDS_HTTP2_MAX_CONCURRENT_STREAMS", default_value_t = 200)]
+    pub http2_max_concurrent_streams: u32,
+
+    /// The interval in seconds of HTTP/2 pings keeping idle connections alive, `0` disables the pings.
+    #[clap(long, env = "MDDS_HTTP2_KEEP_ALIVE_INTERVAL_SECS", default_value_t = 0)]
+    pub http2_keep_alive_interval_secs: u64,
+
+    /// Keep HTTP/1.1 connections open for further requests.
+    #[clap(long, env = "MDDS_HTTP1_KEEP_ALIVE", default_value_t = true, action = clap::ArgAction::Set)]
+    pub http1_keep_alive: bool,
+
+    /// The time in seconds a HTTP/1.1 connection may take to send the headers of its next request,
+    /// i.e. how long an idle keep-alive connection is kept open. `0` keeps idle connections open.
+    #[clap(long, env = "MDDS_KEEP_ALIVE_IDLE_TIMEOUT_SECS", default_value_t = 60)]
+    pub keep_alive_idle_timeout_secs: u64,
+
+    /// The PEM certificate chain to serve TCP with TLS, requires `tls_key`. Reloaded on SIGHUP.
+    #[clap(long, env = "MDDS_TLS_CERT")]
+    pub tls_cert: Option<String>,
+
+    /// The PEM private key of `tls_cert`.
+    #[clap(long, env = "MDDS_TLS_KEY")]
+    pub tls_key: Option<String>,
+
+    /// Compress responses by `Accept-Encoding`, disable if compression is terminated at a proxy.
+    #[clap(long, env = "MDDS_COMPRESSION_ENABLED", default_value_t = true, action = clap::ArgAction::Set)]
+    pub compression_enabled: bool,
+
+    /// The time in seconds a request may take until the response starts, streamed bodies may take longer.
+    #[clap(long, env = "MDDS_REQUEST_TIMEOUT_SECS", default_value_t = 30)]
+    pub request_timeout_secs: u64,
+
+    /// The origins allowed to make cross-origin requests, comma-separated.
+    #[clap(long, env = "MDDS_CORS_ALLOWED_ORIGINS", value_delimiter = ',')]
+    pub cors_allowed_origins: Vec<String>,
+
+    /// Allow cross-origin requests from any origin, only applies if no allowed origins are set.
+    #[clap(long, env = "MDDS_CORS_PERMISSIVE", default_value_t = false, action = clap::ArgAction::Set)]
+    pub cors_permissive: bool,
+
+    /// The address of the http server exposing Prometheus metrics.
+    #[clap(long, env = "MDDS_METRICS_ADDRESS", default_value = "0.0.0.0:9090" )]
+    pub metrics_address: String,
+
     /// The base path for data files.
     #[clap(long, env = "MDDS_PARQUET_DATA_PATH", default_value = "data" )]
     pub parquet_data_path: String,
 
-    // The base path containing the market data files.
+    // The base path containing the market data files, either a local directory or an `s3://bucket/prefix` URL.
     #[clap(long, env = "MDDS_MARKET_DATA_PATH", default_value = "data/market_data" )]
     pub market_data_path: String,
 
+    /// Further local market data directories in priority order after `market_data_path`, comma-separated,
+    /// e.g. a slower archive mount. A date is served from the first path holding a file of it.
+    #[clap(long, env = "MDDS_ARCHIVE_DATA_PATHS", value_delimiter = ',')]
+    pub archive_data_paths: Vec<String>,
+
+    /// The local directory files of an `s3://` market data path are downloaded to before reading.
+    #[clap(long, env = "MDDS_REMOTE_CACHE_PATH", default_value = "data/remote_cache" )]
+    pub remote_cache_path: String,
+
+    /// The local directory compressed files, e.g. `.parquet.gz` or `.parquet.zst`, are decompressed to before reading.
+    #[clap(long, env = "MDDS_DECOMPRESS_CACHE_PATH", default_value = "data/decompress_cache" )]
+    pub decompress_cache_path: String,
+
     /// The size of a record batch when reading parquet files.
     #[clap(long, env = "MDDS_PARQUET_READER_RECORD_BATCH_SIZE", default_value_t = 1024)]
     pub parquet_reader_record_batch_size: usize,
 
+    /// The largest record batch size a request may ask for with `batch_size`.
+    #[clap(long, env = "MDDS_MAX_RECORD_BATCH_SIZE", default_value_t = 65536)]
+    pub max_record_batch_size: usize,
+
+    /// Reads the files of the latest days of every symbol once at startup to warm the page cache, `0` disables the preload.
+    #[clap(long, env = "MDDS_PRELOAD_LATEST_DAYS", default_value_t = 0)]
+    pub preload_latest_days: u32,
+
+    /// The maximum number of parquet files read concurrently across all requests, further reads queue.
+    /// A stream counts as read while opening its files, the open streams are limited by `max_open_files`.
+    #[clap(long, env = "MDDS_MAX_CONCURRENT_READS", default_value_t = 64)]
+    pub max_concurrent_reads: usize,
+
+    /// The maximum number of files open at once, further file opens queue and new streams are rejected.
+    /// A stream reserves the files of two adjacent days at once, at most all of `max_open_files`.
+    /// `0` for half of the process' open files limit (`ulimit -n`).
+    #[clap(long, env = "MDDS_MAX_OPEN_FILES", default_value_t = 0)]
+    pub max_open_files: usize,
+
+    /// The maximum number of parquet files read concurrently by a single batch request.
+    #[clap(long, env = "MDDS_FILE_READ_CONCURRENCY", default_value_t = 4)]
+    pub file_read_concurrency: usize,
+
+    /// The tokio runtime, `multi-thread` or `current-thread` for single-core deployments.
+    #[clap(long, env = "MDDS_RUNTIME_FLAVOR", value_enum, default_value_t = RuntimeFlavor::MultiThread)]
+    pub runtime_flavor: RuntimeFlavor,
+
+    /// The worker threads of the `multi-thread` runtime, `0` for one per CPU core.
+    #[clap(long, env = "MDDS_WORKER_THREADS", default_value_t = 0)]
+    pub worker_threads: usize,
+
+    /// The maximum number of threads for blocking work, e.g. parquet footer reads and offloaded decoding.
+    #[clap(long, env = "MDDS_MAX_BLOCKING_THREADS", default_value_t = 512)]
+    pub max_blocking_threads: usize,
+
+    /// Decode the messages of batch queries on the blocking threads, keeping the async workers free for IO.
+    #[clap(long, env = "MDDS_DECODE_BLOCKING", default_value_t = false, action = clap::ArgAction::Set)]
+    pub decode_blocking: bool,
+
     // The base path containing the market data files.
     #[clap(long, env = "MDDS_PARQUET_FILE_EXTENSION", default_value = "parquet" )]
     pub parquet_file_extension: String,
 
+    /// The `chrono` format of the date in market data file names.
+    #[clap(long, env = "MDDS_FILENAME_DATE_FORMAT", default_value = "%Y-%m-%d" )]
+    pub filename_date_format: String,
+
+    /// The separator between symbol and date in market data file names.
+    #[clap(long, env = "MDDS_FILENAME_SEPARATOR", default_value = "." )]
+    pub filename_separator: String,
+
+    /// The time in seconds a directory scan of a symbol is cached, `0` disables the cache.
+    #[clap(long, env = "MDDS_SCAN_CACHE_TTL_SECS", default_value_t = 60)]
+    pub scan_cache_ttl_secs: u64,
+
+    /// The time in seconds the symbols of a stream are cached for the symbols route, `0` disables the cache.
+    #[clap(long, env = "MDDS_SYMBOL_CACHE_TTL_SECS", default_value_t = 300)]
+    pub symbol_cache_ttl_secs: u64,
+
+    /// The maximum number of messages a client may request per page from the batch endpoint.
+    #[clap(long, env = "MDDS_MAX_PAGE_LIMIT", default_value_t = 10000)]
+    pub max_page_limit: usize,
+
+    /// Pass message data that isn't valid UTF-8 base64 encoded instead of dropping it, unless overridden by `encoding`.
+    #[clap(long, env = "MDDS_BASE64_FALLBACK", default_value_t = false, action = clap::ArgAction::Set)]
+    pub base64_fallback: bool,
+
+    /// Serialize `timestamp_millis` and `timestamp_sec` as JSON strings, for clients that lose precision on large numbers.
+    #[clap(long, env = "MDDS_TIMESTAMP_AS_STRING", default_value_t = false, action = clap::ArgAction::Set)]
+    pub timestamp_as_string: bool,
+
+    /// The maximum number of sub-queries a client may send in a single batch query.
+    /// Also limits the sources of a replay, together with two open files per source within `max_open_files`.
+    #[clap(long, env = "MDDS_MAX_BATCH_QUERIES", default_value_t = 100)]
+    pub max_batch_queries: usize,
+
+    /// The minutes up to the end of the newest file's date queried when `from` and `to` are omitted, unset requires both.
+    #[clap(long, env = "MDDS_DEFAULT_WINDOW_MINUTES")]
+    pub default_window_minutes: Option<u32>,
+
+    /// The maximum number of symbols a glob like `*usdt` may expand to in a single query.
+    #[clap(long, env = "MDDS_MAX_GLOB_SYMBOLS", default_value_t = 50)]
+    pub max_glob_symbols: usize,
+
+    /// The maximum number of days a single query may span from `from` to `to`.
+    #[clap(long, env = "MDDS_MAX_QUERY_RANGE_DAYS", default_value_t = 31)]
+    pub max_query_range_days: u32,
+
+    /// The field in the message data payload carrying the trade price, used to aggregate candles.
+    #[clap(long, env = "MDDS_CANDLE_PRICE_FIELD", default_value = "p" )]
+    pub candle_price_field: String,
+
+    /// The field in the message data payload carrying the trade quantity, used to aggregate candles.
+    #[clap(long, env = "MDDS_CANDLE_QUANTITY_FIELD", default_value = "q" )]
+    pub candle_quantity_field: String,
+
+    /// The number of record batches read ahead of decoding per parquet file, `0` disables prefetching.
+    #[clap(long, env = "MDDS_PARQUET_PREFETCH_BATCHES", default_value_t = 2)]
+    pub parquet_prefetch_batches: usize,
+
+    /// The maximum number of record batches buffered per file when streaming in descending order.
+    #[clap(long, env = "MDDS_DESCENDING_BUFFER_MAX_BATCHES", default_value_t = 1024)]
+    pub descending_buffer_max_batches: usize,
+
+    /// The interval in milliseconds at which WebSocket connections poll their files for appended rows.
+    #[clap(long, env = "MDDS_WS_POLL_INTERVAL_MILLIS", default_value_t = 1000)]
+    pub ws_poll_interval_millis: u64,
+
+    /// The maximum number of attempts to read a parquet file on transient IO errors, `1` disables retries.
+    #[clap(long, env = "MDDS_READ_RETRY_MAX_ATTEMPTS", default_value_t = 3)]
+    pub read_retry_max_attempts: u32,
+
+    /// The backoff in milliseconds before the first retry of a read, doubled on each further retry.
+    #[clap(long, env = "MDDS_READ_RETRY_BACKOFF_MILLIS", default_value_t = 100)]
+    pub read_retry_backoff_millis: u64,
+
+    /// Also read the files of the days adjacent to a queried time range, for messages spilling over into the next day's file.
+    #[clap(long, env = "MDDS_INCLUDE_BOUNDARY_FILES", default_value_t = false, action = clap::ArgAction::Set)]
+    pub include_boundary_files: bool,
+
+    /// The depth of subdirectories below a symbol directory scanned for partitioned files, e.g. `3` for
+    /// `trade/ethusdt/2019/04/05/part-0.parquet`. `0` scans only the files of the stream directory.
+    #[clap(long, env = "MDDS_SCAN_DEPTH", default_value_t = 0)]
+    pub scan_depth: usize,
+
+    /// Skip parquet files whose footer timestamp statistics don't intersect the queried time range.
+    #[clap(long, env = "MDDS_PRUNE_BY_STATISTICS", default_value_t = true, action = clap::ArgAction::Set)]
+    pub prune_by_statistics: bool,
+
+    /// The parquet column holding the message timestamp in milliseconds, e.g. `ts` in older files.
+    /// Files with a differently named column than `timestamp_millis` are read from a renamed copy.
+    #[clap(long, env = "MDDS_PARQUET_TIMESTAMP_COLUMN", default_value = "timestamp_millis" )]
+    pub parquet_timestamp_column: String,
+
+    /// The parquet column holding the message data, files with another name than `data` are read from a renamed copy.
+    #[clap(long, env = "MDDS_PARQUET_DATA_COLUMN", default_value = "data" )]
+    pub parquet_data_column: String,
+
+    /// The API keys clients have to pass as bearer token or `X-API-Key`, comma-separated, no authentication if empty.
+    #[clap(long, env = "MDDS_API_KEYS", value_delimiter = ',', hide_env_values = true)]
+    pub api_keys: Vec<String>,
+
+    /// Serve the `/debug` routes, which echo how queries are resolved including the server's paths.
+    #[clap(long, env = "MDDS_DEBUG_ENDPOINTS", default_value_t = false, action = clap::ArgAction::Set)]
+    pub debug_endpoints: bool,
+
+    /// A JSON file of symbol aliases to the symbols of the market data files, e.g. `{"ETH-USDT": "ethusdt"}`.
+    #[clap(long, env = "MDDS_SYMBOL_ALIASES_PATH")]
+    pub symbol_aliases_path: Option<String>,
+
+    /// Lowercase request paths, to match the lowercase market data directories regardless of the client's casing.
+    #[clap(long, env = "MDDS_LOWERCASE_PATHS", default_value_t = false, action = clap::ArgAction::Set)]
+    pub lowercase_paths: bool,
+
+    /// The exchanges clients may query, comma-separated, any exchange if empty.
+    #[clap(long, env = "MDDS_ALLOWED_EXCHANGES", value_delimiter = ',')]
+    pub allowed_exchanges: Vec<String>,
+
+    /// The streams clients may query, comma-separated, e.g. `trade,depth`, any stream if empty.
+    #[clap(long, env = "MDDS_ALLOWED_STREAMS", value_delimiter = ',')]
+    pub allowed_streams: Vec<String>,
+
+    /// The output format of log lines, `json` for log aggregators or human-readable `text`.
+    #[clap(long, env = "MDDS_LOG_FORMAT", value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+
+    /// The `max-age` in seconds of batch responses ending before the current day, which shared caches
+    /// may keep as their files are complete. `0` marks all responses `no-cache`.
+    #[clap(long, env = "MDDS_HISTORICAL_MAX_AGE_SECS", default_value_t = 86400)]
+    pub historical_max_age_secs: u64,
+
+    /// The maximum number of messages the batch endpoint collects for a response, `0` disables the limit.
+    /// The sub-queries of a batch query share the limit, those beyond it fail.
+    #[clap(long, env = "MDDS_MAX_RESPONSE_MESSAGES", default_value_t = 1_000_000)]
+    pub max_response_messages: usize,
+
+    /// Whether a batch query exceeding `max_response_messages` is rejected with `413 Payload Too Large`
+    /// or answered with its earliest messages and an `x-truncated` header.
+    #[clap(long, env = "MDDS_RESPONSE_LIMIT_MODE", value_enum, default_value_t = ResponseLimitMode::Reject)]
+    pub response_limit_mode: ResponseLimitMode,
+
+    /// How the files are organized below `market_data_path`, either in `nested` directories per stream
+    /// or `flat` with the stream in the file names. Object storage is always nested.
+    #[clap(long, env = "MDDS_DIRECTORY_LAYOUT", value_enum, default_value_t = DirectoryLayout::Nested)]
+    pub directory_layout: DirectoryLayout,
+
+    /// The maximum level of log lines, e.g. `info` or `debug`.
+    #[clap(long, env = "MDDS_LOG_LEVEL", default_value = "debug" )]
+    pub log_level: tracing::Level,
+
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum LogFormat {
+    Json,
+    Text,
+}
+
+/// The layout of the market data files, e.g.
+/// `nested`: `binance/spot/trade/ethusdt.2019-04-05.parquet`,
+/// `flat`: `binance.spot.trade.ethusdt.2019-04-05.parquet`.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum DirectoryLayout {
+    Nested,
+    Flat,
+}
+
+/// What the batch endpoint does with a query exceeding `max_response_messages`.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum ResponseLimitMode {
+    Reject,
+    Truncate,
+}
+
+/// The flavor of the tokio runtime the server runs on.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum RuntimeFlavor {
+    MultiThread,
+    CurrentThread,
 }
\ No newline at end of file
@@ -0,0 +1,106 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use arrow::datatypes::Schema;
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+use sha2::{Digest, Sha256};
+use crate::fs::compression::is_current;
+
+/// The column `s9_parquet::AsyncParquetReader` reads the message timestamp from.
+pub const READER_TIMESTAMP_COLUMN: &str = "timestamp_millis";
+
+/// The column `s9_parquet::AsyncParquetReader` reads the message data from.
+pub const READER_DATA_COLUMN: &str = "data";
+
+/// Distinguishes the partial copies of concurrent reads of the same file.
+static RENAME_ID: AtomicU64 = AtomicU64::new(0);
+
+/// The names of the timestamp and data columns in the market data files, e.g. `ts` in older files.
+#[derive(Clone, Copy, Debug)]
+pub struct ColumnNames<'a> {
+    pub timestamp: &'a str,
+    pub data: &'a str,
+}
+
+impl ColumnNames<'_> {
+
+    fn are_reader_columns(&self) -> bool {
+        self.timestamp == READER_TIMESTAMP_COLUMN && self.data == READER_DATA_COLUMN
+    }
+
+    fn reader_column<'n>(&self, name: &'n str) -> &'n str {
+        if name == self.timestamp {
+            READER_TIMESTAMP_COLUMN
+        } else if name == self.data {
+            READER_DATA_COLUMN
+        } else {
+            name
+        }
+    }
+}
+
+/// The path to read a parquet file from, copying it into `cache_path` with the columns renamed to the ones
+/// of the reader first if it has columns of the configured names.
+///
+/// Files already using the reader's column names are read in place, so archives may mix both. The renamed
+/// copy is reused until the file is modified.
+pub async fn renamed_path(file_path: &Path, cache_path: &str, columns: ColumnNames<'_>) -> anyhow::Result<PathBuf> {
+    if columns.are_reader_columns() {
+        return Ok(file_path.to_path_buf());
+    }
+
+    // Equally named files of different streams must not share a copy
+    let file_name = file_path.file_name().map(|file_name| file_name.to_string_lossy()).unwrap_or_default();
+    let hash = Sha256::digest(file_path.to_string_lossy().as_bytes());
+    let hash: String = hash[..8].iter().map(|byte| format!("{:02x}", byte)).collect();
+    let local_path = PathBuf::from(cache_path).join(format!("{}.renamed.{}", hash, file_name));
+
+    let modified = tokio::fs::metadata(file_path).await?.modified()?;
+    if is_current(&local_path, modified).await {
+        return Ok(local_path);
+    }
+
+    tokio::fs::create_dir_all(cache_path).await?;
+    let rename_id = RENAME_ID.fetch_add(1, Ordering::Relaxed);
+    let partial_path = local_path.with_extension(format!("{}.partial", rename_id));
+    let source_path = file_path.to_path_buf();
+    let target_path = partial_path.clone();
+    let timestamp = columns.timestamp.to_string();
+    let data = columns.data.to_string();
+    let renamed = tokio::task::spawn_blocking(move || {
+        rename_columns(&source_path, &target_path, ColumnNames { timestamp: &timestamp, data: &data })
+    }).await??;
+    if !renamed {
+        return Ok(file_path.to_path_buf());
+    }
+
+    // Renamed once complete, so concurrent reads never open a partial copy
+    tokio::fs::rename(&partial_path, &local_path).await?;
+    Ok(local_path)
+}
+
+/// Writes a copy of the file with the configured columns renamed, returns `false` without writing if it has none of them.
+fn rename_columns(source_path: &Path, target_path: &Path, columns: ColumnNames<'_>) -> anyhow::Result<bool> {
+    let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(source_path)?)?;
+    let schema = Arc::clone(builder.schema());
+    if !schema.fields().iter().any(|field| columns.reader_column(field.name()) != field.name()) {
+        return Ok(false);
+    }
+
+    let fields: Vec<_> = schema
+        .fields()
+        .iter()
+        .map(|field| field.as_ref().clone().with_name(columns.reader_column(field.name())))
+        .collect();
+    let renamed_schema = Arc::new(Schema::new_with_metadata(fields, schema.metadata().clone()));
+
+    let mut writer = ArrowWriter::try_new(File::create(target_path)?, Arc::clone(&renamed_schema), None)?;
+    for batch in builder.build()? {
+        writer.write(&RecordBatch::try_new(Arc::clone(&renamed_schema), batch?.columns().to_vec())?)?;
+    }
+    writer.close()?;
+    Ok(true)
+}
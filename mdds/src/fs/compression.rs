@@ -0,0 +1,100 @@
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+use sha2::{Digest, Sha256};
+
+/// Distinguishes the partial copies of concurrent reads of the same file.
+static DECOMPRESS_ID: AtomicU64 = AtomicU64::new(0);
+
+/// The compression of a whole parquet file, e.g. of `ethusdt.2019-04-05.parquet.gz` in a cold archive.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+
+    /// All supported compressions, to match file names against their suffixes.
+    pub const ALL: [Compression; 2] = [Compression::Gzip, Compression::Zstd];
+
+    /// The file name suffix of the compression, after the parquet file extension.
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            Compression::Gzip => ".gz",
+            Compression::Zstd => ".zst",
+        }
+    }
+
+    /// The compression of a file by the suffix of its name, `None` for a plain parquet file.
+    pub fn of_file_name(file_name: &str) -> Option<Compression> {
+        Compression::ALL.into_iter().find(|compression| file_name.ends_with(compression.suffix()))
+    }
+}
+
+/// Strips a trailing compression suffix from a file name, e.g. `ethusdt.2019-04-05.parquet.gz`.
+pub fn strip_compression_suffix(file_name: &str) -> &str {
+    match Compression::of_file_name(file_name) {
+        Some(compression) => &file_name[..file_name.len() - compression.suffix().len()],
+        None => file_name,
+    }
+}
+
+/// The path to read a parquet file from, decompressing it into `cache_path` first if it is compressed.
+///
+/// As the parquet reader needs to seek, compressed files can't be read as a stream. The decompressed
+/// copy is reused until the compressed file is modified.
+pub async fn decompressed_path(file_path: &Path, cache_path: &str) -> anyhow::Result<PathBuf> {
+    let file_name = file_path.file_name().map(|file_name| file_name.to_string_lossy()).unwrap_or_default();
+    let Some(compression) = Compression::of_file_name(&file_name) else {
+        return Ok(file_path.to_path_buf());
+    };
+
+    // Equally named files of different streams must not share a copy
+    let hash = Sha256::digest(file_path.to_string_lossy().as_bytes());
+    let hash: String = hash[..8].iter().map(|byte| format!("{:02x}", byte)).collect();
+    let local_path = PathBuf::from(cache_path).join(format!("{}.{}", hash, strip_compression_suffix(&file_name)));
+
+    let compressed_modified = tokio::fs::metadata(file_path).await?.modified()?;
+    if is_current(&local_path, compressed_modified).await {
+        return Ok(local_path);
+    }
+
+    tokio::fs::create_dir_all(cache_path).await?;
+    let decompress_id = DECOMPRESS_ID.fetch_add(1, Ordering::Relaxed);
+    let partial_path = local_path.with_extension(format!("{}.partial", decompress_id));
+    let source_path = file_path.to_path_buf();
+    let target_path = partial_path.clone();
+    let decompressed = async {
+        tokio::task::spawn_blocking(move || decompress(compression, &source_path, &target_path)).await??;
+        // Renamed once complete, so concurrent reads never open a partial copy
+        tokio::fs::rename(&partial_path, &local_path).await?;
+        Ok::<_, anyhow::Error>(())
+    };
+    if let Err(err) = decompressed.await {
+        // A failed copy, e.g. of a truncated file, would otherwise stay in the cache for good
+        let _ = tokio::fs::remove_file(&partial_path).await;
+        return Err(err.context(format!("error decompressing {:?}", file_path)));
+    }
+    Ok(local_path)
+}
+
+/// Whether the cached copy of a file is at least as new as the file it was made of.
+pub(super) async fn is_current(local_path: &Path, compressed_modified: SystemTime) -> bool {
+    match tokio::fs::metadata(local_path).await.and_then(|metadata| metadata.modified()) {
+        Ok(modified) => modified >= compressed_modified,
+        Err(_) => false,
+    }
+}
+
+fn decompress(compression: Compression, source_path: &Path, target_path: &Path) -> io::Result<()> {
+    let source = File::open(source_path)?;
+    let mut target = File::create(target_path)?;
+    match compression {
+        Compression::Gzip => io::copy(&mut flate2::read::MultiGzDecoder::new(source), &mut target)?,
+        Compression::Zstd => io::copy(&mut zstd::stream::read::Decoder::new(source)?, &mut target)?,
+    };
+    Ok(())
+}
@@ -1,45 +1,130 @@
-use std::path::PathBuf;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use chrono::NaiveDate;
-use tokio::fs;
-use crate::fs::{FileMetadata, IsWithin, TimeSlice};
+use crate::config::DirectoryLayout;
+use crate::fs::{list_stream_files, stream_file_prefix, walk_files, FileMetadata, IsWithin, SymbolNotFound, TimeSlice};
+use crate::fs::compression::strip_compression_suffix;
+use crate::fs::remote::RemoteStore;
+use crate::fs::scan_cache::{ScanCache, ScanKey};
 
 #[derive(Clone, Copy, Debug)]
 pub struct FileFinder<'a> {
     pub parquet_file_extension: &'a str,
+    /// The `chrono` format of the date in file names, e.g. `%Y-%m-%d`.
+    pub date_format: &'a str,
+    /// The separator between symbol and date in file names, e.g. `.` in `ethusdt.2019-04-05.parquet`.
+    pub separator: &'a str,
+    pub layout: DirectoryLayout,
     pub base_path: &'a str,
+    /// Lower priority local paths of the same layout, e.g. a slower archive, whose files only fill in
+    /// the dates `base_path` lacks.
+    pub archive_paths: &'a [String],
+    /// Lists the files in object storage instead of `base_path`, if configured.
+    pub remote_store: Option<&'a RemoteStore>,
     pub exchange: &'a str,
     pub market_type: &'a str,
     pub stream: &'a str,
     pub symbol: &'a str,
     pub time_slice: &'a TimeSlice<'a>,
+    pub scan_cache: &'a ScanCache,
+    /// Also return the files of the days before and after the time slice, which may hold messages
+    /// of the time slice if they spill over the date in their file name.
+    pub include_boundary_files: bool,
+    /// The depth of subdirectories below the symbol directory to scan, e.g. `3` for
+    /// `trade/ethusdt/2019/04/05/part-0.parquet`, `0` scans the stream directory only.
+    pub scan_depth: usize,
+    /// Re-scan the directory even if a cached scan has not expired yet.
+    pub refresh: bool,
 }
 
 impl FileFinder<'_> {
 
     pub async fn find_files(&self) -> anyhow::Result<Vec<PathBuf>> {
+        let files = self.find_file_metas().await?;
+        Ok(files.into_iter().map(|file_meta| file_meta.path).collect())
+    }
+
+    pub async fn find_file_metas(&self) -> anyhow::Result<Vec<FileMetadata>> {
         // Find, filter and return matching files
-        let files = self.files_for_symbol().await?;
+        let files = self.cached_files_for_symbol().await?;
         let files = self.files_in_time_slice(&files);
         Ok(files)
     }
 
+    /// Returns all files of the symbol sorted by date, regardless of the time slice.
+    pub async fn all_file_metas(&self) -> anyhow::Result<Arc<Vec<FileMetadata>>> {
+        self.cached_files_for_symbol().await
+    }
+
+    /// Returns the dates of the time slice that have no file among the given files.
+    pub fn missing_dates(&self, files: &[FileMetadata]) -> Vec<NaiveDate> {
+        self.time_slice
+            .dates()
+            .filter(|date| !files.iter().any(|file_meta| file_meta.date == *date))
+            .collect()
+    }
+
+    async fn cached_files_for_symbol(&self) -> anyhow::Result<Arc<Vec<FileMetadata>>> {
+        let key = ScanKey {
+            exchange: self.exchange.to_string(),
+            market_type: self.market_type.to_string(),
+            stream: self.stream.to_string(),
+            symbol: self.symbol.to_string(),
+        };
+
+        if !self.refresh {
+            if let Some(files) = self.scan_cache.get(&key) {
+                return Ok(files);
+            }
+        }
+
+        let files = self.files_for_symbol().await?;
+        Ok(self.scan_cache.insert(key, files))
+    }
+
+    /// Returns the files of the symbol across the base and archive paths, a date is taken from the first path holding it.
     async fn files_for_symbol(&self) -> anyhow::Result<Vec<FileMetadata>> {
-        let path = self.path_for_symbol();
+        let mut tiers = vec![self.files_in_tier(self.base_path, self.remote_store).await?];
+        for archive_path in self.archive_paths {
+            tiers.push(self.files_in_tier(archive_path, None).await?);
+        }
+        // A stream directory without any file of the symbol doesn't hold the symbol either
+        if tiers.iter().flatten().all(Vec::is_empty) {
+            return Err(SymbolNotFound { symbol: self.symbol.to_string() }.into());
+        }
 
-        let mut entries = fs::read_dir(&path).await?;
         let mut file_metas = Vec::new();
+        let mut dates = HashSet::new();
+        for tier_files in tiers.into_iter().flatten() {
+            let tier_dates: HashSet<NaiveDate> = tier_files.iter().map(|file_meta| file_meta.date).collect();
+            file_metas.extend(tier_files.into_iter().filter(|file_meta| !dates.contains(&file_meta.date)));
+            dates.extend(tier_dates);
+        }
 
-        let file_prefix = format!("{}.", self.symbol);
-        let file_extension = format!(".{}", self.parquet_file_extension);
+        file_metas.sort_by(|a, b| a.date.cmp(&b.date));
+        Ok(file_metas)
+    }
 
-        while let Some(entry) = entries.next_entry().await? {
-            let filename = entry.file_name();
-            let filename_str = filename.to_string_lossy();
+    /// Returns the files of the symbol below a single base path, `None` if it has no directory of the stream.
+    async fn files_in_tier(&self, base_path: &str, remote_store: Option<&RemoteStore>) -> anyhow::Result<Option<Vec<FileMetadata>>> {
+        // Object storage is always nested
+        let layout = if remote_store.is_some() { DirectoryLayout::Nested } else { self.layout };
+        let listing = list_stream_files(base_path, remote_store, layout, self.exchange, self.market_type, self.stream).await?;
+        let Some((directory, filenames)) = listing else {
+            return Ok(None);
+        };
+        let mut file_metas = Vec::new();
 
-            if let Some(date_str) = self.extract_date_from_filename(&filename_str, &file_prefix, &file_extension) {
-                if let Ok(file_date) = NaiveDate::parse_from_str(&date_str, "%Y-%m-%d") {
+        let stream_prefix = stream_file_prefix(layout, self.separator, self.exchange, self.market_type, self.stream);
+        let file_prefix = format!("{}{}{}", stream_prefix, self.symbol, self.separator);
+        let file_extension = format!(".{}", self.parquet_file_extension);
+
+        for filename in filenames {
+            if let Some(date_str) = self.extract_date_from_filename(&filename, &file_prefix, &file_extension) {
+                if let Ok(file_date) = NaiveDate::parse_from_str(&date_str, self.date_format) {
                     let file_meta = FileMetadata {
-                        path: entry.path(),
+                        path: directory.join(&filename),
                         date: file_date,
                     };
                     file_metas.push(file_meta);
@@ -47,35 +132,232 @@ impl FileFinder<'_> {
             }
         }
 
-        file_metas.sort_by(|a, b| a.date.cmp(&b.date));
+        // Partitioned datasets are only stored locally
+        if self.scan_depth > 0 && remote_store.is_none() {
+            file_metas.extend(self.partitioned_files(&directory.join(self.symbol), &file_prefix, &file_extension).await?);
+        }
+
+        Ok(Some(file_metas))
+    }
+
+    /// Returns the files in the subdirectories of a symbol directory, dated by their file name
+    /// or otherwise by their directories, e.g. `2019-04-05/part-0.parquet` or `2019/04/05/part-0.parquet`.
+    async fn partitioned_files(&self, symbol_directory: &Path, file_prefix: &str, file_extension: &str) -> anyhow::Result<Vec<FileMetadata>> {
+        let mut file_metas = Vec::new();
+        for relative_path in walk_files(symbol_directory, self.scan_depth).await? {
+            let Some(filename) = relative_path.file_name().and_then(|filename| filename.to_str()) else {
+                continue;
+            };
+            if !strip_compression_suffix(filename).ends_with(file_extension) {
+                continue;
+            }
+
+            let file_date = match self.extract_date_from_filename(filename, file_prefix, file_extension) {
+                Some(date_str) => NaiveDate::parse_from_str(&date_str, self.date_format).ok(),
+                None => self.date_from_directories(&relative_path),
+            };
+            if let Some(file_date) = file_date {
+                file_metas.push(FileMetadata {
+                    path: symbol_directory.join(&relative_path),
+                    date: file_date,
+                });
+            }
+        }
         Ok(file_metas)
     }
 
-    fn path_for_symbol(&self) -> String {
-        let mut path = PathBuf::from(self.base_path);
-        path.push(self.exchange);
-        path.push(self.market_type);
-        path.push(self.stream);
-        path.to_string_lossy().to_string()
+    fn date_from_directories(&self, relative_path: &Path) -> Option<NaiveDate> {
+        let directories: Vec<&str> = relative_path
+            .parent()?
+            .iter()
+            .filter_map(|directory| directory.to_str())
+            .collect();
+        if let Some(Ok(date)) = directories.last().map(|directory| NaiveDate::parse_from_str(directory, self.date_format)) {
+            return Some(date);
+        }
+        match directories.as_slice() {
+            [.., year, month, day] => NaiveDate::parse_from_str(&format!("{}-{}-{}", year, month, day), "%Y-%m-%d").ok(),
+            _ => None,
+        }
     }
 
-    fn files_in_time_slice(&self, file_metadata: &Vec<FileMetadata>) -> Vec<PathBuf> {
-        let files: Vec<PathBuf> = file_metadata
+    fn files_in_time_slice(&self, file_metadata: &Vec<FileMetadata>) -> Vec<FileMetadata> {
+        let files: Vec<FileMetadata> = file_metadata
             .iter()
-            .filter(|file_meta| file_meta.date.is_within(self.time_slice))
-            .map(|file_meta| file_meta.path.clone())
+            .filter(|file_meta| {
+                file_meta.date.is_within(self.time_slice) || (self.include_boundary_files && self.is_boundary(file_meta.date))
+            })
+            .cloned()
             .collect();
         files
     }
 
+    fn is_boundary(&self, date: NaiveDate) -> bool {
+        let next_day = date.succ_opt().is_some_and(|next_day| next_day.is_within(self.time_slice));
+        let previous_day = date.pred_opt().is_some_and(|previous_day| previous_day.is_within(self.time_slice));
+        next_day || previous_day
+    }
+
     fn extract_date_from_filename(&self, filename: &str, prefix: &str, file_extension: &str) -> Option<String> {
-        // Extract date from e.g.: ethusdt.2019-04-05.parquet
-        if filename.starts_with(&prefix) && filename.ends_with(&file_extension) {
-            let date = &filename[prefix.len()..filename.len() - file_extension.len()];
-            Some(date.to_string())
-        } else {
-            None
-        }
+        // Extract date from e.g.: ethusdt.2019-04-05.parquet, ethusdt_20190405.parquet or binance.spot.trade.ethusdt.2019-04-05.parquet,
+        // optionally compressed as ethusdt.2019-04-05.parquet.gz
+        // Stripped one after the other, so a short name where prefix and extension overlap has no date
+        // instead of slicing out of bounds
+        let filename = strip_compression_suffix(filename);
+        let date = filename.strip_prefix(prefix)?.strip_suffix(file_extension)?;
+        Some(date.to_string())
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::compression::Compression;
+    use chrono::{DateTime, TimeZone, Utc};
+    use proptest::prelude::*;
+    use std::time::Duration;
+
+    fn file_finder<'a>(
+        base_path: &'a str,
+        date_format: &'a str,
+        separator: &'a str,
+        time_slice: &'a TimeSlice<'a>,
+        scan_cache: &'a ScanCache,
+    ) -> FileFinder<'a> {
+        FileFinder {
+            parquet_file_extension: "parquet",
+            date_format,
+            separator,
+            layout: DirectoryLayout::Nested,
+            base_path,
+            archive_paths: &[],
+            remote_store: None,
+            exchange: "binance",
+            market_type: "spot",
+            stream: "trade",
+            symbol: "ethusdt",
+            time_slice,
+            scan_cache,
+            include_boundary_files: false,
+            scan_depth: 0,
+            refresh: false,
+        }
+    }
+
+    fn day(year: i32, month: u32, day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, 0, 0, 0).unwrap()
+    }
+
+    #[tokio::test]
+    async fn finds_the_files_of_each_date_format_and_separator() {
+        let cases = [
+            ("%Y-%m-%d", ".", "ethusdt.2019-04-05.parquet"),
+            ("%Y-%m-%d", "_", "ethusdt_2019-04-05.parquet"),
+            ("%Y-%m-%d", "-", "ethusdt-2019-04-05.parquet"),
+            ("%Y%m%d", ".", "ethusdt.20190405.parquet"),
+            ("%Y%m%d", "_", "ethusdt_20190405.parquet"),
+            ("%Y%m%d", "-", "ethusdt-20190405.parquet"),
+            ("%d.%m.%Y", "_", "ethusdt_05.04.2019.parquet"),
+            ("%Y-%m-%d", ".", "ethusdt.2019-04-05.parquet.gz"),
+            ("%Y%m%d", "_", "ethusdt_20190405.parquet.zst"),
+        ];
+        let (from, to) = (day(2019, 4, 5), day(2019, 4, 6));
+        let time_slice = TimeSlice { from: &from, to: &to, inclusive_end: false };
+
+        for (date_format, separator, filename) in cases {
+            let base_dir = tempfile::tempdir().unwrap();
+            let stream_dir = base_dir.path().join("binance").join("spot").join("trade");
+            std::fs::create_dir_all(&stream_dir).unwrap();
+            for name in [filename, "btcusdt.2019-04-05.parquet", "ethusdt.2019-04-05.csv", "ethusdt.parquet"] {
+                std::fs::write(stream_dir.join(name), b"").unwrap();
+            }
+
+            let scan_cache = ScanCache::new(Duration::ZERO);
+            let base_path = base_dir.path().to_str().unwrap();
+            let files = file_finder(base_path, date_format, separator, &time_slice, &scan_cache)
+                .find_file_metas()
+                .await
+                .unwrap();
+            let files: Vec<_> = files.iter().map(|file_meta| (file_meta.path.clone(), file_meta.date)).collect();
+            assert_eq!(
+                files,
+                vec![(stream_dir.join(filename), NaiveDate::from_ymd_opt(2019, 4, 5).unwrap())],
+                "{} with {:?}",
+                date_format,
+                separator,
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn finds_no_symbol_in_files_of_another_date_format() {
+        let base_dir = tempfile::tempdir().unwrap();
+        let stream_dir = base_dir.path().join("binance").join("spot").join("trade");
+        std::fs::create_dir_all(&stream_dir).unwrap();
+        std::fs::write(stream_dir.join("ethusdt_20190405.parquet"), b"").unwrap();
+
+        let (from, to) = (day(2019, 4, 5), day(2019, 4, 6));
+        let time_slice = TimeSlice { from: &from, to: &to, inclusive_end: false };
+        let scan_cache = ScanCache::new(Duration::ZERO);
+        let base_path = base_dir.path().to_str().unwrap();
+        let result = file_finder(base_path, "%Y-%m-%d", "_", &time_slice, &scan_cache).find_file_metas().await;
+        assert!(result.unwrap_err().is::<SymbolNotFound>());
+    }
+
+    /// Extracts the date from a file name with the finder of the default configuration.
+    fn extract_date(filename: &str, prefix: &str, file_extension: &str) -> Option<String> {
+        let (from, to) = (day(2019, 4, 5), day(2019, 4, 6));
+        let time_slice = TimeSlice { from: &from, to: &to, inclusive_end: false };
+        let scan_cache = ScanCache::new(Duration::ZERO);
+        file_finder("data", "%Y-%m-%d", ".", &time_slice, &scan_cache).extract_date_from_filename(filename, prefix, file_extension)
+    }
+
+    fn uncompressed_extension() -> impl Strategy<Value = String> {
+        "\\.[a-z0-9]{1,8}".prop_filter("compressed extension", |extension| Compression::of_file_name(extension).is_none())
+    }
+
+    proptest! {
+        #[test]
+        fn extracts_the_date_between_prefix_and_extension(
+            prefix in "[a-z0-9._-]{0,24}",
+            date in "[0-9._-]{0,12}",
+            file_extension in uncompressed_extension(),
+        ) {
+            let filename = format!("{}{}{}", prefix, date, file_extension);
+            prop_assert_eq!(extract_date(&filename, &prefix, &file_extension), Some(date.clone()));
+            for compression in Compression::ALL {
+                let compressed = format!("{}{}", filename, compression.suffix());
+                prop_assert_eq!(extract_date(&compressed, &prefix, &file_extension), Some(date.clone()));
+            }
+        }
+
+        #[test]
+        fn extracts_no_date_where_prefix_and_extension_overlap(
+            prefix in "[a-z0-9._-]{0,12}",
+            overlap in "[a-z0-9._-]{1,12}",
+            file_extension in "[a-z0-9._-]{0,12}",
+        ) {
+            // The overlap is the end of the prefix and the start of the extension at once
+            let filename = format!("{}{}{}", prefix, overlap, file_extension);
+            let prefix = format!("{}{}", prefix, overlap);
+            let file_extension = format!("{}{}", overlap, file_extension);
+            prop_assume!(Compression::of_file_name(&filename).is_none());
+            prop_assert_eq!(extract_date(&filename, &prefix, &file_extension), None);
+        }
+
+        #[test]
+        fn extracts_only_dates_the_file_name_is_made_of(filename in ".{0,32}", prefix in ".{0,16}", file_extension in ".{0,16}") {
+            if let Some(date) = extract_date(&filename, &prefix, &file_extension) {
+                prop_assert_eq!(format!("{}{}{}", prefix, date, file_extension), strip_compression_suffix(&filename));
+            }
+        }
+
+        #[test]
+        fn extracts_no_date_from_short_names(prefix in "[a-z0-9._-]{1,16}", file_extension in uncompressed_extension(), len in 0usize..16) {
+            let name = format!("{}{}", prefix, file_extension);
+            let filename = &name[..len.min(name.len() - 1)];
+            prop_assert_eq!(extract_date(filename, &prefix, &file_extension), None);
+        }
+    }
+}
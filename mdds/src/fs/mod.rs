@@ -1,7 +1,32 @@
+pub mod column_names;
+pub mod compression;
 pub mod file_finder;
+pub mod open_files;
+pub mod parquet_meta;
+pub mod remote;
+pub mod scan_cache;
+pub mod symbol_aliases;
+pub mod symbol_scanner;
 
-use chrono::{DateTime, NaiveDate, Utc};
-use std::path::PathBuf;
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use remote::RemoteStore;
+use crate::config::DirectoryLayout;
+
+/// No market data path holds a file of a symbol, i.e. the symbol was never ingested.
+#[derive(thiserror::Error, Debug)]
+#[error("symbol {symbol} not found")]
+pub struct SymbolNotFound {
+    pub symbol: String,
+}
+
+/// The directory of a stream doesn't exist, i.e. nothing was ever ingested for it.
+#[derive(thiserror::Error, Debug)]
+#[error("stream {stream} not found")]
+pub struct StreamNotFound {
+    pub stream: String,
+}
 
 #[derive(Clone, Debug)]
 pub struct FileMetadata {
@@ -9,11 +34,113 @@ pub struct FileMetadata {
     pub date: NaiveDate,
 }
 
+/// The directory holding the files of all symbols of a stream, e.g. `data/market_data/binance/spot/trade`.
+pub fn stream_directory(base_path: &str, exchange: &str, market_type: &str, stream: &str) -> PathBuf {
+    let mut path = PathBuf::from(base_path);
+    path.push(exchange);
+    path.push(market_type);
+    path.push(stream);
+    path
+}
+
+/// The start of the file names of a stream before the symbol, e.g. `binance.spot.trade.` in the flat layout.
+///
+/// Empty in the nested layout, where the stream is given by the directory instead.
+pub fn stream_file_prefix(layout: DirectoryLayout, separator: &str, exchange: &str, market_type: &str, stream: &str) -> String {
+    match layout {
+        DirectoryLayout::Nested => String::new(),
+        DirectoryLayout::Flat => [exchange, market_type, stream, ""].join(separator),
+    }
+}
+
+/// Lists the files of a directory and its subdirectories up to `max_depth` levels deep, relative to the directory.
+///
+/// A missing directory has no files.
+pub async fn walk_files(directory: &Path, max_depth: usize) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut pending = vec![(PathBuf::new(), 0)];
+    while let Some((relative_directory, depth)) = pending.pop() {
+        let mut entries = match tokio::fs::read_dir(directory.join(&relative_directory)).await {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == ErrorKind::NotFound => continue,
+            Err(err) => return Err(err),
+        };
+        while let Some(entry) = entries.next_entry().await? {
+            let relative_path = relative_directory.join(entry.file_name());
+            let file_type = entry.file_type().await?;
+            if file_type.is_dir() && depth < max_depth {
+                pending.push((relative_path, depth + 1));
+            } else if file_type.is_file() {
+                files.push(relative_path);
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Lists the file names of a stream directory together with the local directory to read them from,
+/// `None` if the stream directory doesn't exist.
+///
+/// In the flat layout, all files of `base_path` are listed, to be filtered by `stream_file_prefix`.
+pub async fn list_stream_files(
+    base_path: &str,
+    remote_store: Option<&RemoteStore>,
+    layout: DirectoryLayout,
+    exchange: &str,
+    market_type: &str,
+    stream: &str,
+) -> anyhow::Result<Option<(PathBuf, Vec<String>)>> {
+    if let Some(remote_store) = remote_store {
+        let file_names = remote_store.list_file_names(exchange, market_type, stream).await?;
+        // Object stores have no directories, a prefix without objects is the closest equivalent
+        if file_names.is_empty() {
+            return Ok(None);
+        }
+        return Ok(Some((remote_store.local_directory(exchange, market_type, stream), file_names)));
+    }
+
+    let directory = match layout {
+        DirectoryLayout::Nested => stream_directory(base_path, exchange, market_type, stream),
+        DirectoryLayout::Flat => PathBuf::from(base_path),
+    };
+    let mut entries = match tokio::fs::read_dir(&directory).await {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    let mut file_names = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        file_names.push(entry.file_name().to_string_lossy().to_string());
+    }
+    Ok(Some((directory, file_names)))
+}
+
 // TODO: Move to separate module
 #[derive(Clone, Copy, Debug)]
 pub struct TimeSlice<'a> {
     pub from: &'a DateTime<Utc>,
     pub to: &'a DateTime<Utc>,
+    /// Whether `to` itself is part of the time slice, otherwise it ends right before.
+    pub inclusive_end: bool,
+}
+
+impl TimeSlice<'_> {
+
+    /// All UTC dates touched by the time slice, in ascending order.
+    pub fn dates(&self) -> impl Iterator<Item = NaiveDate> {
+        let to = self.last_date();
+        self.from.date_naive().iter_days().take_while(move |date| *date <= to)
+    }
+
+    /// The date of the last instant within the time slice, e.g. an exclusive `to` at midnight
+    /// doesn't touch the day it starts.
+    fn last_date(&self) -> NaiveDate {
+        if self.inclusive_end || self.to <= self.from {
+            self.to.date_naive()
+        } else {
+            (*self.to - TimeDelta::nanoseconds(1)).date_naive()
+        }
+    }
 }
 
 // TODO: Move to separate module
@@ -25,7 +152,7 @@ trait IsWithin {
 impl IsWithin for NaiveDate {
     fn is_within(&self, time_slice: &TimeSlice) -> bool {
         let from = time_slice.from.date_naive();
-        let to = time_slice.to.date_naive();
+        let to = time_slice.last_date();
         self >= &from && self <= &to
     }
 }
@@ -0,0 +1,56 @@
+use crate::config::Config;
+
+/// The number of files the server keeps open at once, `max_open_files` or otherwise half of the
+/// process' soft `RLIMIT_NOFILE`, leaving the other half to connections, sockets and logs.
+///
+/// Logs the limit at startup together with the read concurrency, and warns if the configuration
+/// can't be honored within the process limit. `None` if neither is limited.
+pub fn max_open_files(config: &Config) -> Option<usize> {
+    let limit = nofile_limit();
+    match limit {
+        Some((soft, hard)) => tracing::info!(
+            "Open files limit (RLIMIT_NOFILE) soft {}, hard {}, max concurrent reads {}",
+            display_limit(soft), display_limit(hard), config.max_concurrent_reads),
+        None => tracing::info!("Open files limit (RLIMIT_NOFILE) unknown, max concurrent reads {}", config.max_concurrent_reads),
+    }
+    let soft = limit.map(|(soft, _)| soft).filter(|soft| *soft != u64::MAX);
+
+    let max_open_files = match (config.max_open_files, soft) {
+        (0, Some(soft)) => Some((soft / 2).max(1) as usize),
+        (0, None) => None,
+        (max_open_files, Some(soft)) if max_open_files as u64 >= soft => {
+            tracing::warn!("max_open_files {} is not below the soft RLIMIT_NOFILE {}, reads may fail with too many open files",
+                max_open_files, soft);
+            Some(max_open_files)
+        }
+        (max_open_files, _) => Some(max_open_files),
+    };
+    if let Some(max_open_files) = max_open_files {
+        if config.max_concurrent_reads > max_open_files {
+            tracing::warn!("max_concurrent_reads {} exceeds the {} open files, reads queue for files instead",
+                config.max_concurrent_reads, max_open_files);
+        }
+    }
+    max_open_files
+}
+
+fn display_limit(limit: u64) -> String {
+    if limit == u64::MAX { "unlimited".to_string() } else { limit.to_string() }
+}
+
+/// The soft and hard limit of open files of the process, `u64::MAX` if unlimited.
+#[cfg(unix)]
+fn nofile_limit() -> Option<(u64, u64)> {
+    let mut limit = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+    // SAFETY: `getrlimit` only writes the limits to the given struct
+    if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } != 0 {
+        return None;
+    }
+    let as_u64 = |limit: libc::rlim_t| if limit == libc::RLIM_INFINITY { u64::MAX } else { limit as u64 };
+    Some((as_u64(limit.rlim_cur), as_u64(limit.rlim_max)))
+}
+
+#[cfg(not(unix))]
+fn nofile_limit() -> Option<(u64, u64)> {
+    None
+}
@@ -0,0 +1,152 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use parquet::file::statistics::Statistics;
+use parquet::schema::types::Type;
+use serde::Serialize;
+
+/// The number of rows and the min/max timestamp of a row group, from the parquet footer.
+#[derive(Clone, Copy, Debug)]
+pub struct RowGroupTimestamps {
+    pub num_rows: i64,
+    pub min: i64,
+    pub max: i64,
+}
+
+/// Reads the timestamp statistics of all row groups from the footer of a parquet file, without decoding any rows.
+///
+/// Returns `None` if a row group has no statistics for the timestamp column, as its range is unknown then.
+pub async fn row_group_timestamps(path: &Path, timestamp_column: &str) -> anyhow::Result<Option<Vec<RowGroupTimestamps>>> {
+    let path = path.to_path_buf();
+    let timestamp_column = timestamp_column.to_string();
+
+    // The parquet footer reader is blocking, keep it off the async worker threads
+    tokio::task::spawn_blocking(move || -> anyhow::Result<Option<Vec<RowGroupTimestamps>>> {
+        let reader = SerializedFileReader::new(std::fs::File::open(&path)?)?;
+
+        let mut row_groups = Vec::new();
+        for row_group in reader.metadata().row_groups() {
+            let statistics = row_group
+                .columns()
+                .iter()
+                .find(|column| column.column_descr().name() == timestamp_column)
+                .and_then(|column| column.statistics());
+
+            let Some(Statistics::Int64(statistics)) = statistics else {
+                return Ok(None);
+            };
+            let (Some(min), Some(max)) = (statistics.min_opt(), statistics.max_opt()) else {
+                return Ok(None);
+            };
+            row_groups.push(RowGroupTimestamps {
+                num_rows: row_group.num_rows(),
+                min: *min,
+                max: *max,
+            });
+        }
+        Ok(Some(row_groups))
+    }).await?
+}
+
+/// A leaf column of a parquet schema, nested columns are named by their dotted path.
+#[derive(Clone, Debug, Serialize)]
+pub struct ColumnSchema {
+    pub name: String,
+    pub physical_type: String,
+    pub logical_type: Option<String>,
+    pub nullable: bool,
+}
+
+/// The columns and row count of a parquet file, from its footer.
+#[derive(Clone, Debug, Serialize)]
+pub struct FileSchema {
+    pub num_rows: i64,
+    pub num_row_groups: usize,
+    pub columns: Vec<ColumnSchema>,
+}
+
+/// Reads the schema from the footer of a parquet file, without decoding any rows.
+pub async fn file_schema(path: &Path) -> anyhow::Result<FileSchema> {
+    let path = path.to_path_buf();
+
+    tokio::task::spawn_blocking(move || -> anyhow::Result<FileSchema> {
+        let reader = SerializedFileReader::new(std::fs::File::open(&path)?)?;
+        let metadata = reader.metadata();
+
+        let columns = metadata
+            .file_metadata()
+            .schema_descr()
+            .columns()
+            .iter()
+            .map(|column| ColumnSchema {
+                name: column.path().string(),
+                physical_type: column.physical_type().to_string(),
+                logical_type: column.logical_type().map(|logical_type| format!("{:?}", logical_type)),
+                nullable: column.self_type().is_optional(),
+            })
+            .collect();
+
+        Ok(FileSchema {
+            num_rows: metadata.file_metadata().num_rows(),
+            num_row_groups: metadata.num_row_groups(),
+            columns,
+        })
+    }).await?
+}
+
+/// The min/max timestamp over all row groups, `None` if there are no row groups.
+pub fn timestamp_range(row_groups: &[RowGroupTimestamps]) -> Option<(i64, i64)> {
+    let min = row_groups.iter().map(|row_group| row_group.min).min()?;
+    let max = row_groups.iter().map(|row_group| row_group.max).max()?;
+    Some((min, max))
+}
+
+/// The min/max timestamp of a file from its footer statistics, or without statistics by decoding the timestamp column only.
+///
+/// Returns `None` for a file without rows.
+pub async fn file_timestamp_range(path: &Path, timestamp_column: &str) -> anyhow::Result<Option<(i64, i64)>> {
+    if let Some(row_groups) = row_group_timestamps(path, timestamp_column).await? {
+        return Ok(timestamp_range(&row_groups));
+    }
+    let rows = read_columns(path, &[timestamp_column.to_string()]).await?;
+    let range = rows
+        .iter()
+        .filter_map(|row| row.get(timestamp_column)?.as_i64())
+        .fold(None, |range, timestamp| match range {
+            Some((min, max)) => Some((timestamp.min(min), timestamp.max(max))),
+            None => Some((timestamp, timestamp)),
+        });
+    Ok(range)
+}
+
+/// Reads the given top-level columns of all rows of a parquet file as JSON values, in row order.
+///
+/// Only the given columns are decoded, e.g. columns beyond the timestamps and data of the messages.
+pub async fn read_columns(path: &Path, columns: &[String]) -> anyhow::Result<Vec<BTreeMap<String, serde_json::Value>>> {
+    let path = path.to_path_buf();
+    let columns = columns.to_vec();
+
+    tokio::task::spawn_blocking(move || -> anyhow::Result<Vec<BTreeMap<String, serde_json::Value>>> {
+        let reader = SerializedFileReader::new(std::fs::File::open(&path)?)?;
+        let schema = reader.metadata().file_metadata().schema();
+
+        let mut fields = Vec::new();
+        for column in &columns {
+            let field = schema
+                .get_fields()
+                .iter()
+                .find(|field| field.name() == column)
+                .ok_or_else(|| anyhow::anyhow!("column {} not found in {:?}", column, path))?;
+            fields.push(Arc::clone(field));
+        }
+        let projection = Type::group_type_builder(schema.name()).with_fields(fields).build()?;
+
+        let mut rows = Vec::new();
+        for row in reader.get_row_iter(Some(projection))? {
+            let row = row?;
+            rows.push(row.get_column_iter().map(|(name, field)| (name.clone(), field.to_json_value())).collect());
+        }
+        Ok(rows)
+    }).await?
+}
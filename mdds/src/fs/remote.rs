@@ -0,0 +1,98 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use anyhow::Context;
+use object_store::aws::AmazonS3Builder;
+use object_store::path::Path as ObjectPath;
+use object_store::ObjectStore;
+use tokio::fs;
+use crate::fs::stream_directory;
+
+/// Distinguishes the partial downloads of concurrent reads of the same file.
+static DOWNLOAD_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Market data files in object storage, e.g. with `MDDS_MARKET_DATA_PATH=s3://bucket/prefix`.
+///
+/// Files are listed by their object keys, laid out like the local directories. As the parquet
+/// reader expects a filesystem path, files are downloaded to a local cache directory before reading.
+#[derive(Debug)]
+pub struct RemoteStore {
+    store: Box<dyn ObjectStore>,
+    prefix: ObjectPath,
+    cache_path: PathBuf,
+}
+
+impl RemoteStore {
+
+    /// Connects to the object store of an `s3://` market data path, `None` for a local directory.
+    ///
+    /// Credentials and region are taken from the usual `AWS_*` environment variables.
+    pub fn from_market_data_path(market_data_path: &str, cache_path: &str) -> anyhow::Result<Option<Self>> {
+        let Some(location) = market_data_path.strip_prefix("s3://") else {
+            return Ok(None);
+        };
+        let (bucket, prefix) = location.split_once('/').unwrap_or((location, ""));
+
+        let store = AmazonS3Builder::from_env()
+            .with_bucket_name(bucket)
+            .build()
+            .context("error connecting to object store")?;
+
+        Ok(Some(RemoteStore {
+            store: Box::new(store),
+            prefix: ObjectPath::from(prefix.trim_matches('/')),
+            cache_path: PathBuf::from(cache_path),
+        }))
+    }
+
+    /// The local directory the files of a stream are downloaded to.
+    pub fn local_directory(&self, exchange: &str, market_type: &str, stream: &str) -> PathBuf {
+        stream_directory(&self.cache_path.to_string_lossy(), exchange, market_type, stream)
+    }
+
+    /// Lists the file names of a stream, e.g. `ethusdt.2019-04-05.parquet`.
+    pub async fn list_file_names(&self, exchange: &str, market_type: &str, stream: &str) -> anyhow::Result<Vec<String>> {
+        let prefix = self.prefix.child(exchange).child(market_type).child(stream);
+        let listing = self.store.list_with_delimiter(Some(&prefix)).await?;
+        let file_names = listing.objects
+            .into_iter()
+            .filter_map(|object| object.location.filename().map(str::to_string))
+            .collect();
+        Ok(file_names)
+    }
+
+    /// Whether the object store can be listed, i.e. credentials and bucket are valid.
+    pub async fn is_reachable(&self) -> bool {
+        self.store.list_with_delimiter(Some(&self.prefix)).await.is_ok()
+    }
+
+    /// Downloads a file of the local cache directory from the object store, unless the cached copy is current.
+    ///
+    /// Paths outside the cache directory are left alone.
+    pub async fn ensure_local(&self, local_path: &Path) -> anyhow::Result<()> {
+        let Ok(relative_path) = local_path.strip_prefix(&self.cache_path) else {
+            return Ok(());
+        };
+        let location = relative_path
+            .iter()
+            .fold(self.prefix.clone(), |location, part| location.child(part.to_string_lossy().as_ref()));
+
+        let object_meta = self.store.head(&location).await?;
+        if let Ok(metadata) = fs::metadata(local_path).await {
+            // Files of the current day still grow, so a copy of a different size is stale
+            if metadata.len() == object_meta.size {
+                return Ok(());
+            }
+        }
+
+        let bytes = self.store.get(&location).await?.bytes().await?;
+        if let Some(directory) = local_path.parent() {
+            fs::create_dir_all(directory).await?;
+        }
+        // Renamed once complete, so concurrent reads never open a partial copy
+        let download_id = DOWNLOAD_ID.fetch_add(1, Ordering::Relaxed);
+        let partial_path = local_path.with_extension(format!("{}.partial", download_id));
+        fs::write(&partial_path, &bytes).await?;
+        fs::rename(&partial_path, local_path).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::BuildHasher;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use crate::fs::FileMetadata;
+
+/// Identifies the directory of a single symbol's market data files.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ScanKey {
+    pub exchange: String,
+    pub market_type: String,
+    pub stream: String,
+    pub symbol: String,
+}
+
+#[derive(Debug)]
+struct CachedScan {
+    scanned_at: Instant,
+    files: Arc<Vec<FileMetadata>>,
+}
+
+/// In-memory cache of directory scans, so hot symbols don't hit `read_dir` on every request.
+///
+/// Entries expire after the configured TTL, a TTL of zero disables caching.
+#[derive(Debug)]
+pub struct ScanCache {
+    ttl: Duration,
+    entries: RwLock<HashMap<ScanKey, CachedScan>>,
+}
+
+impl ScanCache {
+
+    pub fn new(ttl: Duration) -> Self {
+        ScanCache {
+            ttl,
+            entries: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the cached files for the key, unless the scan has expired.
+    pub fn get(&self, key: &ScanKey) -> Option<Arc<Vec<FileMetadata>>> {
+        let entries = self.entries.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        entries
+            .get(key)
+            .filter(|cached| cached.scanned_at.elapsed() < self.ttl)
+            .map(|cached| Arc::clone(&cached.files))
+    }
+
+    /// Stores the result of a fresh scan, replacing any previous entry for the key.
+    pub fn insert(&self, key: ScanKey, files: Vec<FileMetadata>) -> Arc<Vec<FileMetadata>> {
+        let files = Arc::new(files);
+        if self.ttl.is_zero() {
+            return files;
+        }
+
+        let mut entries = self.entries.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        entries.insert(key, CachedScan {
+            scanned_at: Instant::now(),
+            files: Arc::clone(&files),
+        });
+        files
+    }
+}
+
+/// Identifies a stream directory, whose files are scanned for all symbols at once.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct StreamKey {
+    pub exchange: String,
+    pub market_type: String,
+    pub stream: String,
+}
+
+/// The files of all symbols of a stream, each sorted by date.
+pub type FilesBySymbol = BTreeMap<String, Vec<FileMetadata>>;
+
+#[derive(Debug)]
+struct CachedSymbols {
+    expires_at: Instant,
+    files_by_symbol: Arc<FilesBySymbol>,
+}
+
+/// In-memory cache of the symbols of the streams, as scanning a whole stream directory is expensive
+/// and new symbols are rarely ingested.
+///
+/// The TTL of each entry is extended by a random jitter of up to a tenth, so the entries cached
+/// at startup don't all expire and get re-scanned at once. A TTL of zero disables caching.
+#[derive(Debug)]
+pub struct SymbolCache {
+    ttl: Duration,
+    jitter: RandomState,
+    entries: RwLock<HashMap<StreamKey, CachedSymbols>>,
+}
+
+impl SymbolCache {
+
+    pub fn new(ttl: Duration) -> Self {
+        SymbolCache {
+            ttl,
+            jitter: RandomState::new(),
+            entries: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the cached symbols of the stream, unless the scan has expired.
+    pub fn get(&self, key: &StreamKey) -> Option<Arc<FilesBySymbol>> {
+        let entries = self.entries.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        entries
+            .get(key)
+            .filter(|cached| Instant::now() < cached.expires_at)
+            .map(|cached| Arc::clone(&cached.files_by_symbol))
+    }
+
+    /// Stores the result of a fresh scan, replacing any previous entry for the stream.
+    pub fn insert(&self, key: StreamKey, files_by_symbol: FilesBySymbol) -> Arc<FilesBySymbol> {
+        let files_by_symbol = Arc::new(files_by_symbol);
+        if self.ttl.is_zero() {
+            return files_by_symbol;
+        }
+
+        let now = Instant::now();
+        let jitter = self.ttl / 10 * (self.jitter.hash_one((&key, now)) % 1000) as u32 / 1000;
+        let mut entries = self.entries.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        entries.insert(key, CachedSymbols {
+            expires_at: now + self.ttl + jitter,
+            files_by_symbol: Arc::clone(&files_by_symbol),
+        });
+        files_by_symbol
+    }
+}
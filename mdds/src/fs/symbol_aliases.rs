@@ -0,0 +1,40 @@
+use std::collections::HashMap;
+use anyhow::Context;
+
+/// Translates the symbols clients use, e.g. `ETH-USDT` or `ETH/USDT`, to the canonical name of
+/// the market data files, e.g. `ethusdt`.
+///
+/// Loaded from a JSON object of alias to canonical name, e.g. `{"ETH-USDT": "ethusdt"}`.
+/// Aliases match regardless of case, unknown symbols pass through unchanged.
+#[derive(Debug, Default)]
+pub struct SymbolAliases {
+    aliases: HashMap<String, String>,
+}
+
+impl SymbolAliases {
+
+    pub fn load(path: Option<&str>) -> anyhow::Result<Self> {
+        let Some(path) = path else {
+            return Ok(Self::default());
+        };
+        let json = std::fs::read_to_string(path).with_context(|| format!("error reading symbol aliases {}", path))?;
+        let aliases: HashMap<String, String> = serde_json::from_str(&json)
+            .with_context(|| format!("error parsing symbol aliases {}, expected a JSON object of alias to symbol", path))?;
+        tracing::info!("Loaded {} symbol aliases from {}", aliases.len(), path);
+        Ok(SymbolAliases {
+            aliases: aliases.into_iter().map(|(alias, symbol)| (alias.to_lowercase(), symbol)).collect(),
+        })
+    }
+
+    pub fn resolve<'a>(&'a self, symbol: &'a str) -> &'a str {
+        if self.aliases.is_empty() {
+            return symbol;
+        }
+        self.aliases.get(&symbol.to_lowercase()).map_or(symbol, String::as_str)
+    }
+
+    /// Resolves each symbol of a comma-separated symbol list, e.g. `ETH-USDT,BTC-USDT`.
+    pub fn resolve_list(&self, symbols: &str) -> String {
+        symbols.split(',').map(|symbol| self.resolve(symbol)).collect::<Vec<_>>().join(",")
+    }
+}
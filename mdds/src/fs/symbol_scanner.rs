@@ -0,0 +1,160 @@
+use std::collections::{BTreeMap, HashSet};
+use chrono::NaiveDate;
+use crate::config::DirectoryLayout;
+use crate::fs::{list_stream_files, stream_file_prefix, FileMetadata, StreamNotFound};
+use crate::fs::compression::strip_compression_suffix;
+use crate::fs::remote::RemoteStore;
+
+/// Scans a stream directory for the files of all symbols, e.g. to list what is available on disk.
+#[derive(Clone, Copy, Debug)]
+pub struct SymbolScanner<'a> {
+    pub parquet_file_extension: &'a str,
+    /// The `chrono` format of the date in file names, e.g. `%Y-%m-%d`.
+    pub date_format: &'a str,
+    /// The separator between symbol and date in file names, e.g. `.` in `ethusdt.2019-04-05.parquet`.
+    pub separator: &'a str,
+    pub layout: DirectoryLayout,
+    pub base_path: &'a str,
+    /// Lower priority local paths of the same layout, whose files only fill in the dates `base_path` lacks.
+    pub archive_paths: &'a [String],
+    /// Lists the files in object storage instead of `base_path`, if configured.
+    pub remote_store: Option<&'a RemoteStore>,
+    pub exchange: &'a str,
+    pub market_type: &'a str,
+    pub stream: &'a str,
+}
+
+impl SymbolScanner<'_> {
+
+    /// Returns the files of the stream grouped by symbol, each sorted by date.
+    pub async fn files_by_symbol(&self) -> anyhow::Result<BTreeMap<String, Vec<FileMetadata>>> {
+        let mut files_by_symbol: BTreeMap<String, Vec<FileMetadata>> = BTreeMap::new();
+        for (symbol, file_meta) in self.scan_directory_for_files().await? {
+            files_by_symbol.entry(symbol).or_default().push(file_meta);
+        }
+        for file_metas in files_by_symbol.values_mut() {
+            file_metas.sort_by(|a, b| a.date.cmp(&b.date));
+        }
+        Ok(files_by_symbol)
+    }
+
+    /// Returns the symbols of the stream matching a glob, where `*` matches any characters and `?` a single one.
+    pub async fn symbols_matching(&self, pattern: &str) -> anyhow::Result<Vec<String>> {
+        let files_by_symbol = self.files_by_symbol().await?;
+        Ok(files_by_symbol
+            .into_keys()
+            .filter(|symbol| glob_match(pattern, symbol))
+            .collect())
+    }
+
+    /// Returns all files of the stream directory together with the symbol they belong to.
+    ///
+    /// Of the archive paths, only the files of symbols and dates missing in the paths before are returned.
+    pub async fn scan_directory_for_files(&self) -> anyhow::Result<Vec<(String, FileMetadata)>> {
+        let mut tiers = vec![self.scan_tier(self.base_path, self.remote_store).await?];
+        for archive_path in self.archive_paths {
+            tiers.push(self.scan_tier(archive_path, None).await?);
+        }
+        if tiers.iter().all(Option::is_none) {
+            return Err(StreamNotFound { stream: self.stream.to_string() }.into());
+        }
+
+        let mut files = Vec::new();
+        let mut dates: HashSet<(String, NaiveDate)> = HashSet::new();
+        for tier_files in tiers.into_iter().flatten() {
+            let tier_dates: HashSet<(String, NaiveDate)> = tier_files
+                .iter()
+                .map(|(symbol, file_meta)| (symbol.clone(), file_meta.date))
+                .collect();
+            files.extend(tier_files.into_iter().filter(|(symbol, file_meta)| !dates.contains(&(symbol.clone(), file_meta.date))));
+            dates.extend(tier_dates);
+        }
+        Ok(files)
+    }
+
+    /// Returns the files of the stream directory below a single base path, `None` if it has no such directory.
+    async fn scan_tier(&self, base_path: &str, remote_store: Option<&RemoteStore>) -> anyhow::Result<Option<Vec<(String, FileMetadata)>>> {
+        // Object storage is always nested
+        let layout = if remote_store.is_some() { DirectoryLayout::Nested } else { self.layout };
+        let listing = list_stream_files(base_path, remote_store, layout, self.exchange, self.market_type, self.stream).await?;
+        let Some((directory, filenames)) = listing else {
+            return Ok(None);
+        };
+        let mut files = Vec::new();
+
+        let stream_prefix = stream_file_prefix(layout, self.separator, self.exchange, self.market_type, self.stream);
+        let file_extension = format!(".{}", self.parquet_file_extension);
+
+        for filename in &filenames {
+            // In the flat layout, the directory holds the files of other streams as well
+            let Some(symbol_filename) = filename.strip_prefix(&stream_prefix) else {
+                continue;
+            };
+            if let Some((symbol, file_date)) = self.split_filename(symbol_filename, &file_extension) {
+                let file_meta = FileMetadata {
+                    path: directory.join(filename),
+                    date: file_date,
+                };
+                files.push((symbol.to_string(), file_meta));
+            }
+        }
+
+        Ok(Some(files))
+    }
+
+    fn split_filename<'f>(&self, filename: &'f str, file_extension: &str) -> Option<(&'f str, NaiveDate)> {
+        // Split e.g.: ethusdt.2019-04-05.parquet, ethusdt_20190405.parquet or ethusdt.2019-04-05.parquet.gz
+        // The separator may occur within the date as well, so try each occurrence
+        // until the remainder parses as a date.
+        let stem = strip_compression_suffix(filename).strip_suffix(file_extension)?;
+        if self.separator.is_empty() {
+            return None;
+        }
+        stem.match_indices(self.separator)
+            .filter(|(index, _)| *index > 0)
+            .find_map(|(index, _)| {
+                let date_str = &stem[index + self.separator.len()..];
+                let file_date = NaiveDate::parse_from_str(date_str, self.date_format).ok()?;
+                Some((&stem[..index], file_date))
+            })
+    }
+}
+
+/// Whether the symbol is a glob to be expanded by `SymbolScanner::symbols_matching`.
+pub fn is_glob(symbol: &str) -> bool {
+    symbol.contains(['*', '?'])
+}
+
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    // Backtrack to the last `*` on a mismatch, letting it match one more character
+    let (mut p, mut t) = (0, 0);
+    let mut last_star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                last_star = Some((p, t));
+                p += 1;
+            }
+            Some('?') => {
+                p += 1;
+                t += 1;
+            }
+            Some(c) if *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match last_star {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    last_star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
@@ -0,0 +1,51 @@
+use crate::http::{ApiContext, Error};
+use axum::extract::Request;
+use axum::middleware::Next;
+use axum::response::Response;
+use axum::Extension;
+use http::header::AUTHORIZATION;
+use http::HeaderMap;
+use sha2::{Digest, Sha256};
+
+/// The header to pass the API key in, besides `Authorization: Bearer <key>`.
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Paths of the probes, which have to succeed without a key.
+const UNAUTHENTICATED_PATHS: [&str; 2] = ["/healthz", "/readyz"];
+
+/// Rejects requests without one of the configured API keys with `401 Unauthorized`.
+///
+/// The key is passed either as `Authorization: Bearer <key>` or as `X-API-Key: <key>`.
+/// Without any configured keys, all requests pass for backward compatibility.
+pub async fn require_api_key(ctx: Extension<ApiContext>, request: Request, next: Next) -> Result<Response, Error> {
+    let api_keys = &ctx.config.api_keys;
+    if api_keys.is_empty() || UNAUTHENTICATED_PATHS.contains(&request.uri().path()) {
+        return Ok(next.run(request).await);
+    }
+
+    match api_key(request.headers()) {
+        Some(key) if api_keys.iter().any(|api_key| keys_match(api_key, key)) => Ok(next.run(request).await),
+        // Never log the key itself
+        Some(_) => {
+            tracing::warn!("Rejected request to {} with an invalid API key", request.uri().path());
+            Err(Error::Unauthorized)
+        }
+        None => Err(Error::Unauthorized),
+    }
+}
+
+fn api_key(headers: &HeaderMap) -> Option<&str> {
+    let bearer = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    bearer
+        .or_else(|| headers.get(API_KEY_HEADER).and_then(|value| value.to_str().ok()))
+        .map(str::trim)
+}
+
+/// Compares the SHA-256 digests of the keys, so the time taken doesn't tell how much of a guessed key is right.
+fn keys_match(api_key: &str, key: &str) -> bool {
+    Sha256::digest(api_key.as_bytes()) == Sha256::digest(key.as_bytes())
+}
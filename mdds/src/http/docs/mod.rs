@@ -0,0 +1,12 @@
+use crate::http::market_data::ApiDoc;
+use axum::Router;
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
+
+pub fn router() -> Router {
+    // Example URLs:
+    // localhost:8080/openapi.json
+    // localhost:8080/docs
+    Router::new()
+        .merge(SwaggerUi::new("/docs").url("/openapi.json", ApiDoc::openapi()))
+}
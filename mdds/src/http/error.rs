@@ -2,6 +2,8 @@ use axum::body::Body;
 use axum::http::header::WWW_AUTHENTICATE;
 use axum::http::{HeaderMap, HeaderValue, StatusCode};
 use axum::response::IntoResponse;
+use axum::Json;
+use serde::Serialize;
 
 /// Type alias for [`http::Response`] whose body type defaults to [`Body`], the most common body
 /// type used with axum.
@@ -11,8 +13,8 @@ pub type Response<T = Body> = http::Response<T>;
 /// Can be returned in a `Result` from an API handler function.
 ///
 /// For convenience, this represents both API errors as well as internal recoverable errors,
-/// and maps them to appropriate status codes along with at least a minimally useful error
-/// message in a plain text body, or a JSON body in the case of `UnprocessableEntity`.
+/// and maps them to appropriate status codes along with a JSON body of a stable `code` to switch on
+/// and at least a minimally useful `message`, e.g. `{"error": {"code": "NOT_FOUND", "message": "..."}}`.
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     /// Return `401 Unauthorized`
@@ -27,6 +29,40 @@ pub enum Error {
     #[error("request path not found")]
     NotFound,
 
+    /// Return `400 Bad Request` for invalid path segments or query parameters.
+    #[error("{0}")]
+    BadRequest(String),
+
+    /// Return `404 Not Found` for market data that doesn't exist, e.g. of an unknown symbol.
+    #[error("{0}")]
+    DataNotFound(String),
+
+    /// Return `406 Not Acceptable` for a response format the route doesn't produce.
+    #[error("the requested response format is not available for this route")]
+    NotAcceptable,
+
+    /// Return `413 Payload Too Large` if a query would return more than the configured maximum of messages.
+    #[error("{0}")]
+    PayloadTooLarge(String),
+
+    /// Return `416 Range Not Satisfiable` if the time range lies entirely before or after the files of a symbol.
+    #[error("{0}")]
+    RangeNotSatisfiable(String),
+
+    /// Return `422 Unprocessable Entity` if a `strict` query lacks files for some dates.
+    #[error("{0}")]
+    MissingFiles(String),
+
+    /// Return `500 Internal Server Error` if a parquet file fails to read, e.g. on IO errors.
+    ///
+    /// The cause is logged where the read fails, as it may contain server paths.
+    #[error("error reading market data files")]
+    FileReadFailed,
+
+    /// Return `503 Service Unavailable` if no parquet read could be started before the request timeout.
+    #[error("too many concurrent reads, retry later")]
+    Overloaded,
+
     /// Return `500 Internal Server Error` on a `anyhow::Error`.
     ///
     /// `anyhow::Error` is used in a few places to capture context and backtraces
@@ -48,16 +84,61 @@ impl Error {
             Self::Unauthorized => StatusCode::UNAUTHORIZED,
             Self::Forbidden => StatusCode::FORBIDDEN,
             Self::NotFound => StatusCode::NOT_FOUND,
+            Self::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Self::DataNotFound(_) => StatusCode::NOT_FOUND,
+            Self::NotAcceptable => StatusCode::NOT_ACCEPTABLE,
+            Self::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::RangeNotSatisfiable(_) => StatusCode::RANGE_NOT_SATISFIABLE,
+            Self::MissingFiles(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::FileReadFailed => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Overloaded => StatusCode::SERVICE_UNAVAILABLE,
             Self::Anyhow(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
+
+    /// The stable code of the error, as clients should not rely on the message.
+    fn code(&self) -> &'static str {
+        match self {
+            Self::Unauthorized => "UNAUTHORIZED",
+            Self::Forbidden => "FORBIDDEN",
+            Self::NotFound => "NOT_FOUND",
+            Self::BadRequest(_) => "INVALID_PARAMETER",
+            Self::DataNotFound(_) => "DATA_NOT_FOUND",
+            Self::NotAcceptable => "NOT_ACCEPTABLE",
+            Self::PayloadTooLarge(_) => "PAYLOAD_TOO_LARGE",
+            Self::RangeNotSatisfiable(_) => "RANGE_NOT_SATISFIABLE",
+            Self::MissingFiles(_) => "MISSING_FILES",
+            Self::FileReadFailed => "FILE_READ_FAILED",
+            Self::Overloaded => "OVERLOADED",
+            Self::Anyhow(_) => "INTERNAL_ERROR",
+        }
+    }
+
+    fn body(&self) -> Json<ErrorResponse> {
+        Json(ErrorResponse {
+            error: ErrorBody {
+                code: self.code(),
+                message: self.to_string(),
+            },
+        })
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ErrorResponse {
+    error: ErrorBody,
+}
+
+#[derive(Debug, Serialize)]
+struct ErrorBody {
+    code: &'static str,
+    message: String,
 }
 
 /// Axum allows to return `Result` from handler functions, but the error type
 /// also must be some sort of response type.
 ///
-/// By default, the generated `Display` impl is used to return a plaintext error message
-/// to the client.
+/// By default, the generated `Display` impl is used as the error message returned to the client.
 impl IntoResponse for Error {
     fn into_response(self) -> Response {
         match self {
@@ -67,10 +148,10 @@ impl IntoResponse for Error {
                     // Include the `WWW-Authenticate` challenge required in the specification
                     // for the `401 Unauthorized` response code:
                     // https://developer.mozilla.org/en-US/docs/Web/HTTP/Status/401
-                    [(WWW_AUTHENTICATE, HeaderValue::from_static("Token"))]
+                    [(WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"))]
                         .into_iter()
                         .collect::<HeaderMap>(),
-                    self.to_string(),
+                    self.body(),
                 )
                     .into_response();
             }
@@ -84,7 +165,7 @@ impl IntoResponse for Error {
             _ => (),
         }
 
-        (self.status_code(), self.to_string()).into_response()
+        (self.status_code(), self.body()).into_response()
     }
 }
 
@@ -95,6 +176,7 @@ impl Into<axum::Error> for Error {
             Error::Forbidden => axum::Error::new("user may not perform that action"),
             Error::NotFound => axum::Error::new("request path not found"),
             Error::Anyhow(e) => axum::Error::new(e),
+            error => axum::Error::new(error.to_string()),
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,50 @@
+use crate::http::ApiContext;
+use axum::routing::get;
+use axum::{Extension, Json, Router};
+use http::StatusCode;
+use serde::Serialize;
+
+pub fn router() -> Router {
+    // Example URLs:
+    // localhost:8080/healthz
+    // localhost:8080/readyz
+    Router::new()
+        .route("/healthz", get(health))
+        .route("/readyz", get(ready))
+}
+
+#[derive(Debug, Serialize)]
+struct HealthResponse {
+    status: &'static str,
+}
+
+/// Liveness probe, succeeds as long as the server is able to respond.
+async fn health() -> Json<HealthResponse> {
+    Json(HealthResponse { status: "ok" })
+}
+
+/// Readiness probe, succeeds only once the market data base path is readable,
+/// so no traffic is routed to the server before the data volume is mounted.
+async fn ready(ctx: Extension<ApiContext>) -> (StatusCode, Json<HealthResponse>) {
+    if let Some(remote_store) = &ctx.remote_store {
+        return if remote_store.is_reachable().await {
+            (StatusCode::OK, Json(HealthResponse { status: "ready" }))
+        } else {
+            tracing::warn!("Market data path {} is not reachable", ctx.config.market_data_path);
+            (StatusCode::SERVICE_UNAVAILABLE, Json(HealthResponse { status: "unavailable" }))
+        };
+    }
+    match tokio::fs::metadata(&ctx.config.market_data_path).await {
+        Ok(metadata) if metadata.is_dir() => {
+            (StatusCode::OK, Json(HealthResponse { status: "ready" }))
+        }
+        Ok(_) => {
+            tracing::warn!("Market data path {} is not a directory", ctx.config.market_data_path);
+            (StatusCode::SERVICE_UNAVAILABLE, Json(HealthResponse { status: "unavailable" }))
+        }
+        Err(err) => {
+            tracing::warn!("Market data path {} is not readable: {}", ctx.config.market_data_path, err);
+            (StatusCode::SERVICE_UNAVAILABLE, Json(HealthResponse { status: "unavailable" }))
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use crate::fs::file_finder::FileFinder;
+use crate::fs::{SymbolNotFound, TimeSlice};
+use crate::http::market_data::fields::{FieldSelection, ProjectedMessage};
+use crate::http::market_data::{file_may_contain, find_files, read_parquet_file, validate_path_segment, validate_stream_path, validate_time_range, TimeFilter};
+use crate::codec::Message;
+use crate::http::metrics::HTTP_REQUESTS;
+use crate::http::{ApiContext, Error};
+use axum::{Extension, Json};
+use chrono::{DateTime, Utc};
+use futures::{stream, StreamExt};
+use http::header::CONTENT_TYPE;
+use http::HeaderMap;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A single query of a batch, equivalent to a `GET` of one symbol.
+#[derive(Debug, Deserialize)]
+pub struct SubQuery {
+    exchange: String,
+    market_type: String,
+    stream: String,
+    symbol: String,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    /// Include messages exactly at `to`, by default the time range ends right before it.
+    #[serde(default)]
+    inclusive_end: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BatchResponse {
+    /// The result of each sub-query, keyed by its index in the request.
+    results: BTreeMap<usize, SubQueryResult>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum SubQueryResult {
+    Messages { messages: Vec<ProjectedMessage> },
+    Error { error: String },
+}
+
+/// Runs the sub-queries of a JSON array or NDJSON body, a failing sub-query doesn't fail the others.
+pub async fn query_market_data(
+    ctx: Extension<ApiContext>,
+    headers: HeaderMap,
+    body: String,
+) -> Result<Json<BatchResponse>, Error>
+{
+    metrics::counter!(HTTP_REQUESTS, "route" => "/api/v1/market-data/query").increment(1);
+
+    let sub_queries = parse_sub_queries(&headers, &body).map_err(Error::BadRequest)?;
+    if sub_queries.len() > ctx.config.max_batch_queries {
+        let message = format!("a batch must not exceed {} queries", ctx.config.max_batch_queries);
+        return Err(Error::BadRequest(message));
+    }
+
+    // The messages of all sub-queries are collected for a single response, so they share `max_response_messages`
+    let max_messages = ctx.config.max_response_messages;
+    let mut message_count = 0;
+    let mut results = BTreeMap::new();
+    for (index, sub_query) in sub_queries.iter().enumerate() {
+        let remaining = (max_messages > 0).then(|| max_messages.saturating_sub(message_count));
+        let result = match run_sub_query(&ctx, sub_query, remaining).await {
+            Ok(messages) => {
+                message_count += messages.len();
+                let messages = messages
+                    .into_iter()
+                    .map(|message| ProjectedMessage::new(message, FieldSelection::all(), &ctx.config))
+                    .collect();
+                SubQueryResult::Messages { messages }
+            }
+            Err(error) => SubQueryResult::Error { error: error.to_string() },
+        };
+        results.insert(index, result);
+    }
+
+    Ok(Json(BatchResponse { results }))
+}
+
+fn parse_sub_queries(headers: &HeaderMap, body: &str) -> Result<Vec<SubQuery>, String> {
+    let is_ndjson = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with("application/x-ndjson"));
+
+    if is_ndjson {
+        body.lines()
+            .filter(|line| !line.trim().is_empty())
+            .enumerate()
+            .map(|(index, line)| serde_json::from_str(line).map_err(|err| format!("invalid query in line {}: {}", index + 1, err)))
+            .collect()
+    } else {
+        serde_json::from_str(body).map_err(|err| format!("invalid queries: {}", err))
+    }
+}
+
+/// Fails once more than `max_messages` messages are read, without reading the remaining files.
+async fn run_sub_query(ctx: &Extension<ApiContext>, query: &SubQuery, max_messages: Option<usize>) -> Result<Vec<Message>, Error> {
+    let symbol = ctx.symbol_aliases.resolve(&query.symbol);
+    validate_stream_path(&ctx.config, &query.exchange, &query.market_type, &query.stream)
+        .and_then(|_| validate_path_segment(symbol))
+        .map_err(Error::BadRequest)?;
+    validate_time_range(&query.from, &query.to, ctx.config.max_query_range_days).map_err(Error::BadRequest)?;
+
+    let file_finder = FileFinder {
+        parquet_file_extension: &ctx.config.parquet_file_extension,
+        date_format: &ctx.config.filename_date_format,
+        separator: &ctx.config.filename_separator,
+        layout: ctx.config.directory_layout,
+        base_path: &ctx.config.market_data_path,
+        archive_paths: &ctx.config.archive_data_paths,
+        remote_store: ctx.remote_store.as_deref(),
+        exchange: &query.exchange,
+        market_type: &query.market_type,
+        stream: &query.stream,
+        symbol,
+        time_slice: &TimeSlice {
+            from: &query.from,
+            to: &query.to,
+            inclusive_end: query.inclusive_end,
+        },
+        scan_cache: &ctx.scan_cache,
+        include_boundary_files: ctx.config.include_boundary_files,
+        scan_depth: ctx.config.scan_depth,
+        refresh: false,
+    };
+    let files = match find_files(&file_finder).await {
+        Ok(files) => files,
+        Err(err) if err.is::<SymbolNotFound>() => return Err(Error::DataNotFound(err.to_string())),
+        Err(err) => {
+            // Logged here, as the error of a sub-query is not turned into a response
+            tracing::error!("Error finding files for {}: {:?}", symbol, err);
+            return Err(Error::Anyhow(err.context("Failed to find files")));
+        }
+    };
+
+    let time_filter = TimeFilter::new(query.from, query.to, query.inclusive_end);
+    let mut file_reads = stream::iter(files)
+        .map(|file_meta| async move {
+            if !file_may_contain(&ctx.config, &file_meta.path, &time_filter).await {
+                return Ok(Vec::new());
+            }
+            read_parquet_file(ctx, &file_meta.path, symbol, ctx.config.base64_fallback, ctx.config.parquet_reader_record_batch_size, &[], None).await
+        })
+        .buffer_unordered(ctx.config.file_read_concurrency.max(1));
+
+    let mut messages = Vec::new();
+    while let Some(file_messages) = file_reads.next().await {
+        messages.extend(file_messages?.into_iter().filter(|msg| time_filter.contains_message(msg)));
+        if max_messages.is_some_and(|max_messages| messages.len() > max_messages) {
+            return Err(Error::PayloadTooLarge(format!(
+                "the batch exceeds the maximum of {} messages, use the streaming endpoint /stream/v1/market-data for large time ranges",
+                ctx.config.max_response_messages
+            )));
+        }
+    }
+
+    messages.sort_by_key(|msg| msg.timestamp_millis);
+    Ok(messages)
+}
@@ -0,0 +1,141 @@
+use crate::codec::Message;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// An OHLCV bar aggregating all trades of a symbol within one time bucket.
+#[derive(Debug, Serialize)]
+pub struct Candle {
+    pub symbol: String,
+    /// The start of the time bucket.
+    pub timestamp_millis: i64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: f64,
+}
+
+impl Candle {
+
+    fn new(symbol: &str, timestamp_millis: i64, price: f64, quantity: f64) -> Self {
+        Candle {
+            symbol: symbol.to_string(),
+            timestamp_millis,
+            open: price,
+            high: price,
+            low: price,
+            close: price,
+            volume: quantity,
+        }
+    }
+
+    /// A candle for a bucket without trades, carrying the previous close forward.
+    fn empty(symbol: &str, timestamp_millis: i64, close: f64) -> Self {
+        Self::new(symbol, timestamp_millis, close, 0.0)
+    }
+
+    fn update(&mut self, price: f64, quantity: f64) {
+        self.high = self.high.max(price);
+        self.low = self.low.min(price);
+        self.close = price;
+        self.volume += quantity;
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CandleResponse {
+    pub candles: Vec<Candle>,
+}
+
+/// The names of the fields in the message `data` payload that carry price and quantity.
+#[derive(Clone, Copy, Debug)]
+pub struct CandleFields<'a> {
+    pub price: &'a str,
+    pub quantity: &'a str,
+}
+
+impl CandleFields<'_> {
+
+    fn parse(&self, data: &str) -> Option<(f64, f64)> {
+        let payload: Value = serde_json::from_str(data).ok()?;
+        let price = parse_number(payload.get(self.price)?)?;
+        let quantity = parse_number(payload.get(self.quantity)?)?;
+        Some((price, quantity))
+    }
+}
+
+/// Exchanges commonly send decimals as strings to avoid precision loss, so both are accepted.
+fn parse_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(number) => number.as_f64(),
+        Value::String(string) => string.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Parses an interval like `500ms`, `1s`, `5m`, `1h` or `1d` into milliseconds.
+pub fn parse_interval_millis(interval: &str) -> Option<i64> {
+    let unit_start = interval.find(|c: char| !c.is_ascii_digit())?;
+    let (count, unit) = interval.split_at(unit_start);
+    let count = count.parse::<i64>().ok().filter(|count| *count > 0)?;
+    let unit_millis = match unit {
+        "ms" => 1,
+        "s" => 1_000,
+        "m" => 60_000,
+        "h" => 3_600_000,
+        "d" => 86_400_000,
+        _ => return None,
+    };
+    count.checked_mul(unit_millis)
+}
+
+/// Groups the messages per symbol into buckets of `interval_millis` and aggregates them into candles.
+///
+/// Messages are expected in time order, so the first and last message of a bucket make up its open
+/// and close. Buckets without trades between the first and last trade of a symbol are filled with
+/// the previous close and zero volume, unless `skip_empty` is set.
+pub fn build_candles(messages: &[Message], interval_millis: i64, fields: &CandleFields, skip_empty: bool) -> Vec<Candle> {
+    let mut buckets: BTreeMap<(&str, i64), Candle> = BTreeMap::new();
+    let mut unparseable = 0;
+
+    for message in messages {
+        let Some((price, quantity)) = fields.parse(&message.data) else {
+            unparseable += 1;
+            continue;
+        };
+
+        let bucket_start = message.timestamp_millis.div_euclid(interval_millis) * interval_millis;
+        buckets
+            .entry((message.symbol.as_str(), bucket_start))
+            .and_modify(|candle| candle.update(price, quantity))
+            .or_insert_with(|| Candle::new(&message.symbol, bucket_start, price, quantity));
+    }
+
+    if unparseable > 0 {
+        tracing::warn!("Skipped {} messages without parseable {}/{} fields", unparseable, fields.price, fields.quantity);
+    }
+
+    let mut candles: Vec<Candle> = Vec::with_capacity(buckets.len());
+    for ((symbol, bucket_start), candle) in buckets {
+        if !skip_empty {
+            let previous = candles
+                .last()
+                .filter(|previous| previous.symbol == symbol)
+                .map(|previous| (previous.timestamp_millis, previous.close));
+
+            if let Some((previous_start, previous_close)) = previous {
+                let mut empty_start = previous_start + interval_millis;
+                while empty_start < bucket_start {
+                    candles.push(Candle::empty(symbol, empty_start, previous_close));
+                    empty_start += interval_millis;
+                }
+            }
+        }
+        candles.push(candle);
+    }
+
+    // Buckets are grouped by symbol, interleave them into a single timeline
+    candles.sort_by_key(|candle| candle.timestamp_millis);
+    candles
+}
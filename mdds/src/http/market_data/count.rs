@@ -0,0 +1,127 @@
+use crate::fs::file_finder::FileFinder;
+use crate::fs::{parquet_meta, SymbolNotFound, TimeSlice};
+use crate::http::market_data::{acquire_open_file, find_files, stream_parquet_file, validate_path_segment, validate_stream_path, validate_time_range, QueryParams, TimeFilter};
+use crate::http::metrics::HTTP_REQUESTS;
+use crate::http::{ApiContext, Error};
+use axum::extract::{Path, Query};
+use axum::{Extension, Json};
+use futures::StreamExt;
+use serde::Serialize;
+use std::path::PathBuf;
+
+#[derive(Debug, Serialize)]
+pub struct CountResponse {
+    count: u64,
+}
+
+/// Counts the messages of the time range without decoding their data.
+pub async fn count_market_data(
+    ctx: Extension<ApiContext>,
+    Path((exchange, market_type, stream, symbol)): Path<(String, String, String, String)>,
+    Query(mut query): Query<QueryParams>,
+) -> Result<Json<CountResponse>, Error>
+{
+    metrics::counter!(HTTP_REQUESTS, "route" => "/api/v1/market-data/count").increment(1);
+    let symbol = ctx.symbol_aliases.resolve(&symbol).to_string();
+
+    query.resolve_time_range().map_err(Error::BadRequest)?;
+
+    validate_stream_path(&ctx.config, &exchange, &market_type, &stream)
+        .and_then(|_| validate_path_segment(&symbol))
+        .map_err(Error::BadRequest)?;
+    let Some((from, to)) = query.from.zip(query.to) else {
+        return Err(Error::BadRequest("Missing from/to parameters".to_string()));
+    };
+    validate_time_range(&from, &to, ctx.config.max_query_range_days)
+        .map_err(Error::BadRequest)?;
+    let batch_size = query.record_batch_size(&ctx.config)
+        .map_err(Error::BadRequest)?;
+
+    let file_finder = FileFinder {
+        parquet_file_extension: &ctx.config.parquet_file_extension,
+        date_format: &ctx.config.filename_date_format,
+        separator: &ctx.config.filename_separator,
+        layout: ctx.config.directory_layout,
+        base_path: &ctx.config.market_data_path,
+        archive_paths: &ctx.config.archive_data_paths,
+        remote_store: ctx.remote_store.as_deref(),
+        exchange: &exchange,
+        market_type: &market_type,
+        stream: &stream,
+        symbol: &symbol,
+        time_slice: &TimeSlice {
+            from: &from,
+            to: &to,
+            inclusive_end: query.inclusive_end,
+        },
+        scan_cache: &ctx.scan_cache,
+        include_boundary_files: ctx.config.include_boundary_files,
+        scan_depth: ctx.config.scan_depth,
+        refresh: query.refresh,
+    };
+
+    let files = match find_files(&file_finder).await {
+        Ok(files) => files,
+        Err(err) if err.is::<SymbolNotFound>() => return Err(Error::DataNotFound(err.to_string())),
+        Err(err) => return Err(Error::Anyhow(err.context("Failed to find files"))),
+    };
+
+    let time_filter = TimeFilter::new(from, to, query.inclusive_end);
+    let mut count = 0;
+    for file_meta in files {
+        count += count_file(&ctx, &file_meta.path, &time_filter, batch_size).await?;
+    }
+
+    Ok(Json(CountResponse { count }))
+}
+
+/// Counts the messages of a file within the time filter.
+///
+/// Row groups entirely inside or outside of the time range are counted from the footer statistics.
+/// Only if a row group straddles a bound, the timestamps of the file are read, but never the data decoded.
+async fn count_file(ctx: &ApiContext, file_path: &PathBuf, time_filter: &TimeFilter, batch_size: usize) -> Result<u64, Error> {
+    let row_groups = if ctx.config.prune_by_statistics {
+        parquet_meta::row_group_timestamps(file_path, &ctx.config.parquet_timestamp_column).await
+            .unwrap_or_else(|err| {
+                tracing::debug!("Unable to read timestamp statistics of {:?}: {}", file_path, err);
+                None
+            })
+    } else {
+        None
+    };
+
+    if let Some(row_groups) = row_groups {
+        let mut count = 0;
+        let mut straddles = false;
+        for row_group in &row_groups {
+            if time_filter.covers_millis(row_group.min, row_group.max) {
+                count += row_group.num_rows.max(0) as u64;
+            } else if time_filter.overlaps_millis(row_group.min, row_group.max) {
+                straddles = true;
+                break;
+            }
+        }
+        if !straddles {
+            return Ok(count);
+        }
+    }
+
+    let _open_file = acquire_open_file(ctx).await.map_err(|err| {
+        tracing::error!("Error reading parquet file: {}", err);
+        Error::Overloaded
+    })?;
+    let entry_stream = stream_parquet_file(ctx, file_path, batch_size).await?;
+    let mut entry_stream = Box::pin(entry_stream);
+
+    let mut count = 0;
+    while let Some(entry) = entry_stream.next().await {
+        let entry = entry.map_err(|err| {
+            tracing::error!("Error reading entry from {:?}: {}", file_path, err);
+            Error::FileReadFailed
+        })?;
+        if time_filter.contains_entry(&entry) {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
@@ -0,0 +1,109 @@
+use crate::config::DirectoryLayout;
+use crate::fs::file_finder::FileFinder;
+use crate::fs::{stream_directory, FileMetadata, SymbolNotFound, TimeSlice};
+use crate::http::market_data::{find_files, validate_path_segment, validate_stream_path, QueryParams};
+use crate::http::{ApiContext, Error};
+use axum::extract::{Path, Query};
+use axum::{Extension, Json};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use std::path::PathBuf;
+
+#[derive(Debug, Serialize)]
+pub struct EchoResponse {
+    exchange: String,
+    market_type: String,
+    stream: String,
+    /// The symbol after resolving aliases.
+    symbol: String,
+    /// The directories scanned for the files of the symbol, the market data path first and then the archive paths.
+    directories: Vec<PathBuf>,
+    /// Whether the files are listed in object storage instead of the market data path.
+    remote: bool,
+    /// The UTC time range as resolved from `from`/`to`, `tz`, `today` or `window`.
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    inclusive_end: bool,
+    files: Vec<EchoFile>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EchoFile {
+    path: PathBuf,
+    date: NaiveDate,
+}
+
+/// Echoes how a query of the market data endpoints is interpreted, without reading any file.
+///
+/// Only routed with `debug_endpoints`, as the response exposes the server's paths.
+pub async fn echo(
+    ctx: Extension<ApiContext>,
+    Path((exchange, market_type, stream, symbol)): Path<(String, String, String, String)>,
+    Query(mut query): Query<QueryParams>,
+) -> Result<Json<EchoResponse>, Error>
+{
+    let symbol = ctx.symbol_aliases.resolve(&symbol).to_string();
+    query.resolve_time_range().map_err(Error::BadRequest)?;
+    validate_stream_path(&ctx.config, &exchange, &market_type, &stream)
+        .and_then(|_| validate_path_segment(&symbol))
+        .map_err(Error::BadRequest)?;
+
+    let directories = std::iter::once(&ctx.config.market_data_path)
+        .chain(&ctx.config.archive_data_paths)
+        .map(|base_path| match ctx.config.directory_layout {
+            DirectoryLayout::Nested => stream_directory(base_path, &exchange, &market_type, &stream),
+            DirectoryLayout::Flat => PathBuf::from(base_path),
+        })
+        .collect();
+
+    let files = match query.from.zip(query.to) {
+        Some((from, to)) => {
+            let file_finder = FileFinder {
+                parquet_file_extension: &ctx.config.parquet_file_extension,
+                date_format: &ctx.config.filename_date_format,
+                separator: &ctx.config.filename_separator,
+                layout: ctx.config.directory_layout,
+                base_path: &ctx.config.market_data_path,
+                archive_paths: &ctx.config.archive_data_paths,
+                remote_store: ctx.remote_store.as_deref(),
+                exchange: &exchange,
+                market_type: &market_type,
+                stream: &stream,
+                symbol: &symbol,
+                time_slice: &TimeSlice {
+                    from: &from,
+                    to: &to,
+                    inclusive_end: query.inclusive_end,
+                },
+                scan_cache: &ctx.scan_cache,
+                include_boundary_files: ctx.config.include_boundary_files,
+                scan_depth: ctx.config.scan_depth,
+                refresh: query.refresh,
+            };
+            match find_files(&file_finder).await {
+                Ok(files) => files,
+                // The directories already tell where the symbol was looked for
+                Err(err) if err.is::<SymbolNotFound>() => Vec::new(),
+                Err(err) => return Err(Error::Anyhow(err.context("Failed to find files"))),
+            }
+        }
+        // Without a time range there are no files to select
+        None => Vec::new(),
+    };
+
+    Ok(Json(EchoResponse {
+        exchange,
+        market_type,
+        stream,
+        symbol,
+        directories,
+        remote: ctx.remote_store.is_some(),
+        from: query.from,
+        to: query.to,
+        inclusive_end: query.inclusive_end,
+        files: files
+            .into_iter()
+            .map(|FileMetadata { path, date }| EchoFile { path, date })
+            .collect(),
+    }))
+}
@@ -0,0 +1,298 @@
+use crate::fs::file_finder::FileFinder;
+use crate::fs::{SymbolNotFound, TimeSlice};
+use crate::http::market_data::format::PARQUET_CONTENT_TYPE;
+use crate::http::market_data::{acquire_open_file, fetch_remote_file, find_files, validate_path_segment, validate_stream_path, validate_time_range, QueryParams};
+use crate::http::metrics::HTTP_REQUESTS;
+use crate::http::{ApiContext, Error};
+use axum::body::{Body, Bytes};
+use axum::extract::{Path, Query};
+use axum::response::{IntoResponse, Response};
+use axum::Extension;
+use chrono::DateTime;
+use futures::{future, stream, StreamExt, TryStreamExt};
+use http::header::{ACCEPT_RANGES, CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, IF_RANGE, LAST_MODIFIED, RANGE};
+use http::{HeaderMap, StatusCode};
+use std::io::SeekFrom;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio::sync::Semaphore;
+use tokio_util::io::ReaderStream;
+
+const TAR_CONTENT_TYPE: &str = "application/x-tar";
+const TAR_BLOCK_SIZE: u64 = 512;
+
+/// A file to download, with the size and modification time at the time of the request.
+struct DownloadFile {
+    path: PathBuf,
+    name: String,
+    size: u64,
+    mtime: u64,
+}
+
+/// The part of a file requested by the `Range` header.
+enum ByteRange {
+    Full,
+    Partial(Range<u64>),
+    Unsatisfiable,
+}
+
+/// Returns the parquet files of the time range as they are, without decoding them.
+///
+/// A single file is streamed as is, multiple files are streamed as a tar archive.
+/// A single file also serves a byte range by the `Range` header, so interrupted downloads can be resumed.
+pub async fn download_market_data(
+    ctx: Extension<ApiContext>,
+    Path((exchange, market_type, stream, symbol)): Path<(String, String, String, String)>,
+    Query(mut query): Query<QueryParams>,
+    headers: HeaderMap,
+) -> Result<Response, Error>
+{
+    metrics::counter!(HTTP_REQUESTS, "route" => "/api/v1/market-data/download").increment(1);
+    let symbol = ctx.symbol_aliases.resolve(&symbol).to_string();
+
+    query.resolve_time_range().map_err(Error::BadRequest)?;
+
+    validate_stream_path(&ctx.config, &exchange, &market_type, &stream)
+        .and_then(|_| validate_path_segment(&symbol))
+        .map_err(Error::BadRequest)?;
+    let Some((from, to)) = query.from.zip(query.to) else {
+        return Err(Error::BadRequest("Missing from/to parameters".to_string()));
+    };
+    validate_time_range(&from, &to, ctx.config.max_query_range_days)
+        .map_err(Error::BadRequest)?;
+
+    let file_finder = FileFinder {
+        parquet_file_extension: &ctx.config.parquet_file_extension,
+        date_format: &ctx.config.filename_date_format,
+        separator: &ctx.config.filename_separator,
+        layout: ctx.config.directory_layout,
+        base_path: &ctx.config.market_data_path,
+        archive_paths: &ctx.config.archive_data_paths,
+        remote_store: ctx.remote_store.as_deref(),
+        exchange: &exchange,
+        market_type: &market_type,
+        stream: &stream,
+        symbol: &symbol,
+        time_slice: &TimeSlice {
+            from: &from,
+            to: &to,
+            inclusive_end: query.inclusive_end,
+        },
+        scan_cache: &ctx.scan_cache,
+        include_boundary_files: ctx.config.include_boundary_files,
+        scan_depth: ctx.config.scan_depth,
+        refresh: query.refresh,
+    };
+
+    let file_metas = match find_files(&file_finder).await {
+        Ok(file_metas) => file_metas,
+        Err(err) if err.is::<SymbolNotFound>() => return Err(Error::DataNotFound(err.to_string())),
+        Err(err) => return Err(Error::Anyhow(err.context("Failed to find files"))),
+    };
+    if file_metas.is_empty() {
+        return Err(Error::DataNotFound("No files found for the time range".to_string()));
+    }
+
+    let mut files = Vec::with_capacity(file_metas.len());
+    for file_meta in file_metas {
+        files.push(download_file(&ctx, file_meta.path).await.map_err(|err| {
+            tracing::error!("Error preparing download: {}", err);
+            Error::FileReadFailed
+        })?);
+    }
+
+    if let [file] = files.as_slice() {
+        let (status, range) = match byte_range(&headers, file) {
+            ByteRange::Full => (StatusCode::OK, 0..file.size),
+            ByteRange::Partial(range) => (StatusCode::PARTIAL_CONTENT, range),
+            ByteRange::Unsatisfiable => {
+                let message = format!("the range is outside of the {} bytes of the file", file.size);
+                let headers = [(CONTENT_RANGE, format!("bytes */{}", file.size))];
+                return Ok((headers, Error::RangeNotSatisfiable(message)).into_response());
+            }
+        };
+        let permit = acquire_open_file(&ctx).await.map_err(|err| {
+            tracing::error!("Error opening {:?}: {}", file.path, err);
+            Error::Overloaded
+        })?;
+        let content = ReaderStream::new(open_file_range(file, &range).await.map_err(|err| {
+            tracing::error!("Error opening {:?}: {}", file.path, err);
+            Error::FileReadFailed
+        })?);
+        // The file counts as open until the body is dropped
+        let body = Body::from_stream(content.map(move |chunk| {
+            let _permit = &permit;
+            chunk
+        }));
+        let mut response = (
+            status,
+            [
+                (CONTENT_TYPE, PARQUET_CONTENT_TYPE.to_string()),
+                (CONTENT_LENGTH, (range.end - range.start).to_string()),
+                (CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", file.name)),
+                (ACCEPT_RANGES, "bytes".to_string()),
+                (LAST_MODIFIED, http_date(file.mtime)),
+            ],
+            body,
+        ).into_response();
+        if status == StatusCode::PARTIAL_CONTENT {
+            if let Ok(content_range) = format!("bytes {}-{}/{}", range.start, range.end - 1, file.size).parse() {
+                response.headers_mut().insert(CONTENT_RANGE, content_range);
+            }
+        }
+        return Ok(response);
+    }
+
+    let archive_name = format!("{}.{}.{}.{}.{}_{}.tar", exchange, market_type, stream, symbol, from.date_naive(), to.date_naive());
+    let headers = [
+        (CONTENT_TYPE, TAR_CONTENT_TYPE.to_string()),
+        (CONTENT_LENGTH, tar_size(&files).to_string()),
+        (CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", archive_name)),
+    ];
+    Ok((headers, tar_body(files, Arc::clone(&ctx.open_files))).into_response())
+}
+
+async fn download_file(ctx: &ApiContext, path: PathBuf) -> anyhow::Result<DownloadFile> {
+    fetch_remote_file(ctx, &path).await.map_err(|err| anyhow::anyhow!(err))?;
+    let metadata = tokio::fs::metadata(&path).await?;
+    let mtime = metadata.modified()?.duration_since(UNIX_EPOCH).map(|mtime| mtime.as_secs()).unwrap_or(0);
+    let name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    Ok(DownloadFile { path, name, size: metadata.len(), mtime })
+}
+
+/// Opens the file limited to its size at the time of the request,
+/// as the file of the current day may still be appended to while it is sent.
+async fn open_file(file: &DownloadFile) -> std::io::Result<tokio::io::Take<tokio::fs::File>> {
+    Ok(tokio::fs::File::open(&file.path).await?.take(file.size))
+}
+
+/// Opens the file limited to the byte range, which lies within its size at the time of the request.
+async fn open_file_range(file: &DownloadFile, range: &Range<u64>) -> std::io::Result<tokio::io::Take<tokio::fs::File>> {
+    let mut reader = tokio::fs::File::open(&file.path).await?;
+    reader.seek(SeekFrom::Start(range.start)).await?;
+    Ok(reader.take(range.end - range.start))
+}
+
+/// The byte range requested by a single `bytes` range of the `Range` header, e.g. `bytes=1024-`, `bytes=0-1023` or `bytes=-1024`.
+///
+/// Multiple ranges and malformed headers are ignored and the full file is sent, as RFC 9110 allows.
+/// So is a range of an `If-Range` not matching the modification time, as the client's part is of another version of the file.
+fn byte_range(headers: &HeaderMap, file: &DownloadFile) -> ByteRange {
+    let Some(range) = headers.get(RANGE).and_then(|range| range.to_str().ok()) else {
+        return ByteRange::Full;
+    };
+    if let Some(if_range) = headers.get(IF_RANGE).and_then(|if_range| if_range.to_str().ok()) {
+        let matches = DateTime::parse_from_rfc2822(if_range).is_ok_and(|if_range| if_range.timestamp() == file.mtime as i64);
+        if !matches {
+            return ByteRange::Full;
+        }
+    }
+    let Some((start, end)) = range.trim().strip_prefix("bytes=").and_then(|range| range.split_once('-')) else {
+        return ByteRange::Full;
+    };
+    if end.contains(',') {
+        return ByteRange::Full;
+    }
+
+    match (start.trim(), end.trim()) {
+        // The last bytes of the file
+        ("", suffix) => match suffix.parse::<u64>() {
+            Ok(0) => ByteRange::Unsatisfiable,
+            Ok(suffix) => ByteRange::Partial(file.size.saturating_sub(suffix)..file.size),
+            Err(_) => ByteRange::Full,
+        },
+        (start, end) => {
+            let Ok(start) = start.parse::<u64>() else {
+                return ByteRange::Full;
+            };
+            let end = match end {
+                "" => file.size,
+                end => match end.parse::<u64>() {
+                    Ok(end) if end >= start => (end + 1).min(file.size),
+                    _ => return ByteRange::Full,
+                },
+            };
+            if start >= file.size {
+                ByteRange::Unsatisfiable
+            } else {
+                ByteRange::Partial(start..end)
+            }
+        }
+    }
+}
+
+/// Formats seconds since the epoch as HTTP date, e.g. `Wed, 15 Oct 2025 16:21:30 GMT`.
+fn http_date(secs: u64) -> String {
+    DateTime::from_timestamp(secs as i64, 0)
+        .map(|date| date.format("%a, %d %b %Y %H:%M:%S GMT").to_string())
+        .unwrap_or_default()
+}
+
+/// Streams the files as an uncompressed ustar archive, parquet files are compressed already.
+///
+/// The files are opened one after the other, each once any of `open_files` is free.
+fn tar_body(files: Vec<DownloadFile>, open_files: Arc<Semaphore>) -> Body {
+    let entries = stream::iter(files)
+        .then(move |file| {
+            let open_files = Arc::clone(&open_files);
+            async move {
+                let header = Bytes::copy_from_slice(&tar_header(&file));
+                let padding = Bytes::from(vec![0; tar_padding(file.size) as usize]);
+                let permit = open_files.acquire_owned().await.map_err(std::io::Error::other)?;
+                let content = ReaderStream::new(open_file(&file).await?).map(move |chunk| {
+                    let _permit = &permit;
+                    chunk
+                });
+                Ok::<_, std::io::Error>(
+                    stream::once(future::ready(Ok(header)))
+                        .chain(content)
+                        .chain(stream::once(future::ready(Ok(padding)))),
+                )
+            }
+        })
+        .try_flatten();
+    // An archive ends with two empty blocks
+    let end = stream::once(future::ready(Ok(Bytes::from(vec![0; 2 * TAR_BLOCK_SIZE as usize]))));
+    Body::from_stream(entries.chain(end))
+}
+
+fn tar_size(files: &[DownloadFile]) -> u64 {
+    let entries: u64 = files.iter().map(|file| TAR_BLOCK_SIZE + file.size + tar_padding(file.size)).sum();
+    entries + 2 * TAR_BLOCK_SIZE
+}
+
+fn tar_padding(size: u64) -> u64 {
+    (TAR_BLOCK_SIZE - size % TAR_BLOCK_SIZE) % TAR_BLOCK_SIZE
+}
+
+fn tar_header(file: &DownloadFile) -> [u8; TAR_BLOCK_SIZE as usize] {
+    let mut header = [0; TAR_BLOCK_SIZE as usize];
+    // Market data file names are far below the 100 bytes of the name field
+    let name = file.name.as_bytes();
+    let name_len = name.len().min(100);
+    header[..name_len].copy_from_slice(&name[..name_len]);
+    write_octal(&mut header[100..108], 0o644);
+    write_octal(&mut header[108..116], 0);
+    write_octal(&mut header[116..124], 0);
+    write_octal(&mut header[124..136], file.size);
+    write_octal(&mut header[136..148], file.mtime);
+    header[156] = b'0';
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+
+    // The checksum is computed with the checksum field itself set to spaces
+    header[148..156].fill(b' ');
+    let checksum: u64 = header.iter().map(|byte| u64::from(*byte)).sum();
+    write_octal(&mut header[148..155], checksum);
+    header
+}
+
+/// Writes the value as zero-padded octal digits, terminated by NUL.
+fn write_octal(field: &mut [u8], value: u64) {
+    let digits = format!("{:0width$o}", value, width = field.len() - 1);
+    field[..field.len() - 1].copy_from_slice(&digits.as_bytes()[digits.len() - (field.len() - 1)..]);
+    field[field.len() - 1] = 0;
+}
@@ -0,0 +1,85 @@
+use crate::http::market_data::format::{self, PARQUET_CONTENT_TYPE};
+use crate::fs::SymbolNotFound;
+use crate::http::market_data::{open_files_exhausted, s_market_entries, OpenFiles, validate_path_segment, validate_stream_path, validate_time_range, QueryParams};
+use crate::http::metrics::{ActiveStreamGuard, HTTP_REQUESTS};
+use crate::http::{ApiContext, Error};
+use axum::body::Body;
+use axum::extract::{Path, Query};
+use axum::response::{IntoResponse, Response};
+use axum::Extension;
+use futures::{stream, StreamExt};
+use http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+
+/// Returns the messages of the time range as a single parquet file, merged from the files of all days
+/// and filtered to the time range, with the timestamp columns and `data` of the market data files.
+///
+/// The file is encoded while it is sent, one row group at a time.
+pub async fn export_market_data(
+    ctx: Extension<ApiContext>,
+    Path((exchange, market_type, stream, symbol)): Path<(String, String, String, String)>,
+    Query(mut query): Query<QueryParams>,
+) -> Result<Response, Error>
+{
+    metrics::counter!(HTTP_REQUESTS, "route" => "/api/v1/market-data/export").increment(1);
+    let symbol = ctx.symbol_aliases.resolve(&symbol).to_string();
+
+    query.resolve_time_range().map_err(Error::BadRequest)?;
+
+    validate_stream_path(&ctx.config, &exchange, &market_type, &stream)
+        .and_then(|_| validate_path_segment(&symbol))
+        .map_err(Error::BadRequest)?;
+    let Some((from, to)) = query.from.zip(query.to) else {
+        return Err(Error::BadRequest("Missing from/to parameters".to_string()));
+    };
+    validate_time_range(&from, &to, ctx.config.max_query_range_days)
+        .map_err(Error::BadRequest)?;
+    let batch_size = query.record_batch_size(&ctx.config)
+        .map_err(Error::BadRequest)?;
+    if open_files_exhausted(&ctx) {
+        tracing::warn!("Rejecting export, all files of max_open_files are in use");
+        return Err(Error::Overloaded);
+    }
+
+    let file_name = format!("{}.{}.{}.{}.{}_{}.parquet", exchange, market_type, stream, symbol, from.date_naive(), to.date_naive());
+    let active_streams = ctx.active_streams.clone();
+    let path = Path((exchange, market_type, stream, symbol));
+    let (file_count, mut entries) = s_market_entries(ctx, path, Query(query), OpenFiles::Reserve).await;
+
+    // The status is sent before the file, so an error of the first read is answered here
+    // instead of truncating the body
+    let first = match entries.next().await {
+        Some(Err(err)) => return Err(entry_error(err)),
+        first => first,
+    };
+    if first.is_none() && file_count == 0 {
+        return Err(Error::DataNotFound("No files found for the time range".to_string()));
+    }
+    let entries = stream::iter(first).chain(entries).boxed();
+
+    // The export is active until its body is dropped, like the streams
+    let guard = ActiveStreamGuard::new(&active_streams);
+    let chunks = format::parquet_body_stream(entries, batch_size).into_data_stream().map(move |chunk| {
+        let _guard = &guard;
+        chunk
+    });
+    let headers = [
+        (CONTENT_TYPE, PARQUET_CONTENT_TYPE.to_string()),
+        (CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", file_name)),
+    ];
+    Ok((headers, Body::from_stream(chunks)).into_response())
+}
+
+
+/// Maps an error of reading the entries to its response, keeping the `Error` of a read, e.g. `Overloaded`.
+fn entry_error(err: anyhow::Error) -> Error {
+    if err.is::<SymbolNotFound>() {
+        return Error::DataNotFound(err.to_string());
+    }
+    match err.downcast::<Error>() {
+        Ok(err) => err,
+        Err(err) => {
+            tracing::error!("Error reading entries for export: {:#}", err);
+            Error::FileReadFailed
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use crate::config::Config;
+use crate::codec::Message;
+use serde::ser::{SerializeMap, Serializer};
+use serde::Serialize;
+
+/// The `Message` fields that can be selected with the `fields` parameter, in serialization order.
+pub const MESSAGE_FIELDS: [&str; 8] = [
+    "exchange",
+    "market_type",
+    "stream",
+    "symbol",
+    "timestamp_millis",
+    "timestamp_sec",
+    "timestamp_sub_sec",
+    "data",
+];
+
+/// The set of `Message` fields to serialize, one bit per entry of `MESSAGE_FIELDS`.
+#[derive(Clone, Copy, Debug)]
+pub struct FieldSelection(u8);
+
+impl FieldSelection {
+
+    pub fn all() -> Self {
+        Self(u8::MAX)
+    }
+
+    /// Parses a comma-separated list of field names, e.g. `timestamp_millis,data`.
+    pub fn parse(fields: &str) -> Result<Self, String> {
+        let mut selection = 0u8;
+        for field in fields.split(',').map(str::trim).filter(|field| !field.is_empty()) {
+            match MESSAGE_FIELDS.iter().position(|name| *name == field) {
+                Some(index) => selection |= 1 << index,
+                None => return Err(format!("unknown field {}, valid fields are: {}", field, MESSAGE_FIELDS.join(", "))),
+            }
+        }
+        if selection == 0 {
+            return Err(format!("fields must not be empty, valid fields are: {}", MESSAGE_FIELDS.join(", ")));
+        }
+        Ok(Self(selection))
+    }
+
+    fn contains(&self, index: usize) -> bool {
+        self.0 & (1 << index) != 0
+    }
+}
+
+/// A message that serializes only the selected fields.
+#[derive(Debug)]
+pub struct ProjectedMessage {
+    pub(super) message: Message,
+    pub(super) fields: FieldSelection,
+    /// Serialize the timestamps as strings, for clients that parse JSON numbers as doubles.
+    pub(super) timestamps_as_string: bool,
+    /// Serialize UTF-8 data that is valid JSON as nested value instead of as string.
+    pub(super) raw_json: bool,
+}
+
+impl ProjectedMessage {
+
+    pub(super) fn new(message: Message, fields: FieldSelection, config: &Config) -> Self {
+        ProjectedMessage {
+            message,
+            fields,
+            timestamps_as_string: config.timestamp_as_string,
+            raw_json: false,
+        }
+    }
+
+    pub(super) fn with_raw_json(mut self, raw_json: bool) -> Self {
+        self.raw_json = raw_json;
+        self
+    }
+}
+
+impl Serialize for ProjectedMessage {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let message = &self.message;
+        let mut map = serializer.serialize_map(None)?;
+        for (index, name) in MESSAGE_FIELDS.iter().enumerate() {
+            if !self.fields.contains(index) {
+                continue;
+            }
+            match *name {
+                // Meta fields are only present if requested with `include_meta`
+                "exchange" => if let Some(exchange) = &message.exchange { map.serialize_entry(name, exchange)? },
+                "market_type" => if let Some(market_type) = &message.market_type { map.serialize_entry(name, market_type)? },
+                "stream" => if let Some(stream) = &message.stream { map.serialize_entry(name, stream)? },
+                "symbol" => map.serialize_entry(name, &message.symbol)?,
+                "timestamp_millis" if self.timestamps_as_string => map.serialize_entry(name, &message.timestamp_millis.to_string())?,
+                "timestamp_millis" => map.serialize_entry(name, &message.timestamp_millis)?,
+                "timestamp_sec" if self.timestamps_as_string => map.serialize_entry(name, &message.timestamp_sec.to_string())?,
+                "timestamp_sec" => map.serialize_entry(name, &message.timestamp_sec)?,
+                "timestamp_sub_sec" => map.serialize_entry(name, &message.timestamp_sub_sec)?,
+                "data" => {
+                    // Base64 data may happen to be valid JSON, e.g. a number, so only UTF-8 data is embedded
+                    let value = if self.raw_json && message.encoding.is_utf8() {
+                        serde_json::from_str::<serde_json::Value>(&message.data).ok()
+                    } else {
+                        None
+                    };
+                    match value {
+                        Some(value) => map.serialize_entry(name, &value)?,
+                        // Not JSON, e.g. a plain text payload, stays a string
+                        None => map.serialize_entry(name, &message.data)?,
+                    }
+                    // Clients can't decode base64 data without knowing about it
+                    if !message.encoding.is_utf8() {
+                        map.serialize_entry("encoding", &message.encoding)?;
+                    }
+                }
+                _ => unreachable!("all message fields are covered"),
+            }
+        }
+        if let Some(columns) = &message.columns {
+            map.serialize_entry("columns", columns)?;
+        }
+        map.end()
+    }
+}
@@ -0,0 +1,296 @@
+use crate::codec::Message;
+use arrow::array::{ArrayRef, BinaryArray, Int32Array, Int64Array};
+use arrow::record_batch::RecordBatch;
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::ipc::writer::StreamWriter;
+use axum::body::{Body, Bytes};
+use futures::stream::BoxStream;
+use futures::{stream, Stream, StreamExt};
+use http::header::ACCEPT;
+use http::HeaderMap;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::{Compression, ZstdLevel};
+use parquet::file::properties::WriterProperties;
+use s9_parquet::Entry;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The media type of the Apache Arrow IPC streaming format.
+pub const ARROW_STREAM_CONTENT_TYPE: &str = "application/vnd.apache.arrow.stream";
+
+/// The media type of parquet files.
+pub const PARQUET_CONTENT_TYPE: &str = "application/vnd.apache.parquet";
+
+/// The rows of a row group of an exported parquet file, which is buffered until it is complete.
+const PARQUET_ROW_GROUP_SIZE: usize = 65536;
+
+/// The response representations supported by the market data endpoints.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ResponseFormat {
+    Json,
+    Csv,
+    Arrow,
+    /// Server-Sent Events, one `data:` frame per message.
+    EventStream,
+}
+
+impl ResponseFormat {
+    /// Picks the first supported media type listed in the `Accept` header, defaulting to JSON.
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        headers
+            .get_all(ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .find_map(|media_range| {
+                let media_type = media_range.split(';').next().unwrap_or_default().trim();
+                if media_type.eq_ignore_ascii_case(mime::TEXT_CSV.essence_str()) {
+                    Some(Self::Csv)
+                } else if media_type.eq_ignore_ascii_case(mime::APPLICATION_JSON.essence_str()) {
+                    Some(Self::Json)
+                } else if media_type.eq_ignore_ascii_case(ARROW_STREAM_CONTENT_TYPE) {
+                    Some(Self::Arrow)
+                } else if media_type.eq_ignore_ascii_case(mime::TEXT_EVENT_STREAM.essence_str()) {
+                    Some(Self::EventStream)
+                } else {
+                    None
+                }
+            })
+            .unwrap_or(Self::Json)
+    }
+}
+
+const CSV_HEADER: [&str; 5] = ["symbol", "timestamp_millis", "timestamp_sec", "timestamp_sub_sec", "data"];
+
+/// Encodes all messages as a CSV document including the header row.
+pub fn csv_body(messages: &[Message]) -> anyhow::Result<Vec<u8>> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(CSV_HEADER)?;
+    for message in messages {
+        write_csv_record(&mut writer, message)?;
+    }
+    Ok(writer.into_inner().map_err(|err| err.into_error())?)
+}
+
+/// Encodes a stream of messages as CSV, writing the header row once followed by one line per message.
+pub fn csv_body_stream(messages: impl Stream<Item = anyhow::Result<Message>> + Send + 'static) -> Body {
+    let header = stream::once(async {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer.write_record(CSV_HEADER)?;
+        into_bytes(writer)
+    });
+
+    let rows = messages.map(|result| {
+        let message = result?;
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        write_csv_record(&mut writer, &message)?;
+        into_bytes(writer)
+    });
+
+    Body::from_stream(header.chain(rows))
+}
+
+/// The state of `buffered_body`, an error is held back until the data read before it is flushed.
+struct BufferState {
+    chunks: BoxStream<'static, Result<Bytes, axum::Error>>,
+    pending_error: Option<axum::Error>,
+}
+
+/// Coalesces the chunks of a streamed body, e.g. one per message, into fewer and larger writes.
+///
+/// A write is flushed once it holds `flush_count` chunks, or `flush_interval` after its first chunk,
+/// so slow streams are still sent promptly. The rest is flushed at the end of the stream.
+pub fn buffered_body(body: Body, flush_count: usize, flush_interval: Duration) -> Body {
+    let state = BufferState {
+        chunks: body.into_data_stream().boxed(),
+        pending_error: None,
+    };
+
+    let buffered = stream::unfold(state, move |mut state| async move {
+        if let Some(err) = state.pending_error.take() {
+            return Some((Err(err), state));
+        }
+
+        // Wait for the first chunk without a deadline, an idle stream has nothing to flush
+        let mut buffer = match state.chunks.next().await? {
+            Ok(chunk) => chunk.to_vec(),
+            Err(err) => return Some((Err(err), state)),
+        };
+        let mut count = 1;
+        let deadline = tokio::time::Instant::now() + flush_interval;
+
+        while count < flush_count {
+            match tokio::time::timeout_at(deadline, state.chunks.next()).await {
+                Ok(Some(Ok(chunk))) => {
+                    buffer.extend_from_slice(&chunk);
+                    count += 1;
+                }
+                Ok(Some(Err(err))) => {
+                    state.pending_error = Some(err);
+                    break;
+                }
+                // The end of the stream or the flush interval has been reached
+                Ok(None) | Err(_) => break,
+            }
+        }
+        Some((Ok(Bytes::from(buffer)), state))
+    });
+
+    Body::from_stream(buffered)
+}
+
+/// Writes a single message as a CSV record, quoting the `data` field as needed since payloads
+/// commonly contain commas and quotes.
+fn write_csv_record<W: io::Write>(writer: &mut csv::Writer<W>, message: &Message) -> csv::Result<()> {
+    writer.write_field(&message.symbol)?;
+    writer.write_field(message.timestamp_millis.to_string())?;
+    writer.write_field(message.timestamp_sec.to_string())?;
+    writer.write_field(message.timestamp_sub_sec.to_string())?;
+    writer.write_field(&message.data)?;
+    writer.write_record(None::<&[u8]>)
+}
+
+fn into_bytes(writer: csv::Writer<Vec<u8>>) -> anyhow::Result<Bytes> {
+    let buffer = writer.into_inner().map_err(|err| err.into_error())?;
+    Ok(Bytes::from(buffer))
+}
+
+/// The Arrow schema of streamed entries, with `data` as binary column so payloads are never decoded.
+fn entry_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("timestamp_millis", DataType::Int64, false),
+        Field::new("timestamp_sec", DataType::Int64, false),
+        Field::new("timestamp_sub_sec", DataType::Int32, false),
+        Field::new("data", DataType::Binary, false),
+    ]))
+}
+
+enum ArrowStreamState {
+    Schema(BoxStream<'static, Vec<anyhow::Result<Entry>>>),
+    Batches(BoxStream<'static, Vec<anyhow::Result<Entry>>>, StreamWriter<Vec<u8>>),
+    Done,
+}
+
+/// Encodes a stream of entries in the Arrow IPC streaming format.
+///
+/// The schema is written once at stream start, followed by one record batch per `batch_size`
+/// entries, each flushed to the client as soon as it is encoded.
+pub fn arrow_body_stream(entries: impl Stream<Item = anyhow::Result<Entry>> + Send + 'static, batch_size: usize) -> Body {
+    let chunks = entries.chunks(batch_size.max(1)).boxed();
+
+    let encoded = stream::unfold(ArrowStreamState::Schema(chunks), |state| async move {
+        match state {
+            ArrowStreamState::Schema(chunks) => {
+                match StreamWriter::try_new(Vec::new(), &entry_schema()) {
+                    Ok(mut writer) => {
+                        let bytes = take_bytes(&mut writer);
+                        Some((Ok(bytes), ArrowStreamState::Batches(chunks, writer)))
+                    }
+                    Err(err) => Some((Err(anyhow::Error::from(err)), ArrowStreamState::Done)),
+                }
+            }
+            ArrowStreamState::Batches(mut chunks, mut writer) => {
+                let result = match chunks.next().await {
+                    Some(chunk) => write_chunk(&mut writer, chunk),
+                    None => {
+                        // Write the end-of-stream marker
+                        let result = writer.finish().map_err(anyhow::Error::from);
+                        return Some((result.map(|_| take_bytes(&mut writer)), ArrowStreamState::Done));
+                    }
+                };
+
+                match result {
+                    Ok(()) => {
+                        let bytes = take_bytes(&mut writer);
+                        Some((Ok(bytes), ArrowStreamState::Batches(chunks, writer)))
+                    }
+                    Err(err) => Some((Err(err), ArrowStreamState::Done)),
+                }
+            }
+            ArrowStreamState::Done => None,
+        }
+    });
+
+    Body::from_stream(encoded)
+}
+
+enum ParquetStreamState {
+    Batches(BoxStream<'static, Vec<anyhow::Result<Entry>>>, ArrowWriter<Vec<u8>>),
+    Done,
+}
+
+/// Encodes a stream of entries as a single parquet file of the `entry_schema`.
+///
+/// Each row group is sent once it is complete, so at most a single row group is held in memory,
+/// followed by the footer at the end of the stream.
+pub fn parquet_body_stream(entries: impl Stream<Item = anyhow::Result<Entry>> + Send + 'static, batch_size: usize) -> Body {
+    let chunks = entries.chunks(batch_size.max(1)).boxed();
+    let properties = WriterProperties::builder()
+        .set_max_row_group_size(PARQUET_ROW_GROUP_SIZE)
+        .set_compression(Compression::ZSTD(ZstdLevel::default()))
+        .build();
+    let state = match ArrowWriter::try_new(Vec::new(), entry_schema(), Some(properties)) {
+        Ok(writer) => ParquetStreamState::Batches(chunks, writer),
+        Err(err) => return Body::from_stream(stream::once(async move { Err::<Bytes, _>(anyhow::Error::from(err)) })),
+    };
+
+    let encoded = stream::unfold(state, |state| async move {
+        match state {
+            ParquetStreamState::Batches(mut chunks, mut writer) => {
+                let result = match chunks.next().await {
+                    Some(chunk) => chunk
+                        .into_iter()
+                        .collect::<anyhow::Result<Vec<Entry>>>()
+                        .and_then(|entries| Ok(entries_to_record_batch(&entries)?))
+                        .and_then(|batch| Ok(writer.write(&batch)?)),
+                    None => {
+                        // Write the footer with the metadata of all row groups
+                        let result = writer.finish().map_err(anyhow::Error::from);
+                        return Some((result.map(|_| Bytes::from(std::mem::take(writer.inner_mut()))), ParquetStreamState::Done));
+                    }
+                };
+
+                match result {
+                    // Empty until a row group is complete
+                    Ok(()) => {
+                        let bytes = Bytes::from(std::mem::take(writer.inner_mut()));
+                        Some((Ok(bytes), ParquetStreamState::Batches(chunks, writer)))
+                    }
+                    Err(err) => Some((Err(err), ParquetStreamState::Done)),
+                }
+            }
+            ParquetStreamState::Done => None,
+        }
+    });
+
+    // Skip the empty chunks of row groups still being buffered
+    Body::from_stream(encoded.filter(|result| std::future::ready(!matches!(result, Ok(bytes) if bytes.is_empty()))))
+}
+
+fn write_chunk(writer: &mut StreamWriter<Vec<u8>>, chunk: Vec<anyhow::Result<Entry>>) -> anyhow::Result<()> {
+    let entries = chunk.into_iter().collect::<anyhow::Result<Vec<Entry>>>()?;
+    let batch = entries_to_record_batch(&entries)?;
+    writer.write(&batch)?;
+    Ok(())
+}
+
+fn entries_to_record_batch(entries: &[Entry]) -> Result<RecordBatch, arrow::error::ArrowError> {
+    let timestamp_millis = Int64Array::from_iter_values(entries.iter().map(|entry| entry.timestamp_info.timestamp_millis));
+    let timestamp_sec = Int64Array::from_iter_values(entries.iter().map(|entry| entry.timestamp_info.timestamp_sec));
+    let timestamp_sub_sec = Int32Array::from_iter_values(entries.iter().map(|entry| entry.timestamp_info.timestamp_sub_sec));
+    let data = BinaryArray::from_iter_values(entries.iter().map(|entry| entry.data.as_slice()));
+
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(timestamp_millis),
+        Arc::new(timestamp_sec),
+        Arc::new(timestamp_sub_sec),
+        Arc::new(data),
+    ];
+    RecordBatch::try_new(entry_schema(), columns)
+}
+
+/// Takes the bytes encoded so far out of the writer's buffer.
+fn take_bytes(writer: &mut StreamWriter<Vec<u8>>) -> Bytes {
+    Bytes::from(std::mem::take(writer.get_mut()))
+}
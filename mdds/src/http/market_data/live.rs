@@ -0,0 +1,218 @@
+use crate::fs::file_finder::FileFinder;
+use crate::fs::{SymbolNotFound, TimeSlice};
+use crate::http::market_data::fields::{FieldSelection, ProjectedMessage};
+use crate::http::market_data::{acquire_open_file, decode_entry, open_files_exhausted, stream_parquet_file, validate_path_segment, validate_stream_path, validate_time_range, QueryParams, TimeFilter};
+use crate::http::metrics::{ActiveStreamGuard, HTTP_REQUESTS};
+use crate::http::{ApiContext, Error};
+use axum::extract::ws::{Message as WsMessage, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path, Query};
+use axum::response::{IntoResponse, Response};
+use axum::Extension;
+use chrono::{DateTime, NaiveDate, Utc};
+use futures::StreamExt;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::time::Duration;
+
+pub async fn ws_market_data(
+    ws: WebSocketUpgrade,
+    ctx: Extension<ApiContext>,
+    Path((exchange, market_type, stream, symbol)): Path<(String, String, String, String)>,
+    Query(mut query): Query<QueryParams>,
+) -> Response
+{
+    metrics::counter!(HTTP_REQUESTS, "route" => "/ws/v1/market-data").increment(1);
+    let symbol = ctx.symbol_aliases.resolve(&symbol).to_string();
+
+    // Validate parameters before upgrading the connection
+    if let Err(message) = query.resolve_time_range() {
+        return Error::BadRequest(message).into_response();
+    }
+    if let Err(message) = validate_stream_path(&ctx.config, &exchange, &market_type, &stream)
+        .and_then(|_| validate_path_segment(&symbol)) {
+        return Error::BadRequest(message).into_response();
+    }
+    let Some((from, to)) = query.from.zip(query.to) else {
+        return Error::BadRequest("Missing from/to parameters".to_string()).into_response();
+    };
+    if let Err(message) = validate_time_range(&from, &to, ctx.config.max_query_range_days) {
+        return Error::BadRequest(message).into_response();
+    }
+    let batch_size = match query.record_batch_size(&ctx.config) {
+        Ok(batch_size) => batch_size,
+        Err(message) => return Error::BadRequest(message).into_response(),
+    };
+    if open_files_exhausted(&ctx) {
+        return Error::Overloaded.into_response();
+    }
+
+    tracing::info!("tailing market data for {}/{}/{}/{}", exchange, market_type, stream, symbol);
+
+    let base64_fallback = query.base64_fallback(&ctx.config);
+    let tail = Tail {
+        ctx,
+        exchange,
+        market_type,
+        stream,
+        symbol,
+        include_meta: query.include_meta,
+        base64_fallback,
+        batch_size,
+        from,
+        to,
+        inclusive_end: query.inclusive_end,
+        rows_read: HashMap::new(),
+        completed: HashSet::new(),
+    };
+
+    ws.on_upgrade(move |socket| async move {
+        let _guard = ActiveStreamGuard::new(&tail.ctx.active_streams);
+        tail.run(socket).await
+    })
+}
+
+/// A connection that replays the files of a time range and then tails them for appended rows.
+///
+/// Parquet files can only be read from the start, so appended rows are found by re-reading a file
+/// and skipping the rows already sent.
+struct Tail {
+    ctx: Extension<ApiContext>,
+    exchange: String,
+    market_type: String,
+    stream: String,
+    symbol: String,
+    include_meta: bool,
+    base64_fallback: bool,
+    batch_size: usize,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    inclusive_end: bool,
+    /// The number of rows already read per file.
+    rows_read: HashMap<PathBuf, usize>,
+    /// Files of past days, which don't grow anymore once they have been read.
+    completed: HashSet<PathBuf>,
+}
+
+impl Tail {
+
+    async fn run(mut self, mut socket: WebSocket) {
+        let poll_interval = Duration::from_millis(self.ctx.config.ws_poll_interval_millis);
+        let time_filter = TimeFilter::new(self.from, self.to, self.inclusive_end);
+        // Only files of the current day or later are still being appended to
+        let live_since = Utc::now().date_naive();
+        let mut refresh = false;
+
+        loop {
+            let pass_started = Utc::now();
+            if let Err(err) = self.send_appended(&mut socket, &time_filter, live_since, refresh).await {
+                tracing::warn!("Stopped tailing market data for {}: {}", self.symbol, err);
+                return;
+            }
+            // Re-scan the directory on later passes to pick up files of a new day
+            refresh = true;
+
+            // All messages up to `to` have been sent, nothing left to tail
+            if pass_started > self.to {
+                break;
+            }
+
+            if !wait_unless_closed(&mut socket, poll_interval).await {
+                return;
+            }
+        }
+
+        let _ = socket.send(WsMessage::Close(None)).await;
+    }
+
+    /// Sends the matching rows appended to the files of the time range since the previous pass.
+    async fn send_appended(
+        &mut self,
+        socket: &mut WebSocket,
+        time_filter: &TimeFilter,
+        live_since: NaiveDate,
+        refresh: bool,
+    ) -> anyhow::Result<()>
+    {
+        let file_finder = FileFinder {
+            parquet_file_extension: &self.ctx.config.parquet_file_extension,
+            date_format: &self.ctx.config.filename_date_format,
+            separator: &self.ctx.config.filename_separator,
+            layout: self.ctx.config.directory_layout,
+            base_path: &self.ctx.config.market_data_path,
+            archive_paths: &self.ctx.config.archive_data_paths,
+            remote_store: self.ctx.remote_store.as_deref(),
+            exchange: &self.exchange,
+            market_type: &self.market_type,
+            stream: &self.stream,
+            symbol: &self.symbol,
+            time_slice: &TimeSlice {
+                from: &self.from,
+                to: &self.to,
+                inclusive_end: self.inclusive_end,
+            },
+            scan_cache: &self.ctx.scan_cache,
+            include_boundary_files: self.ctx.config.include_boundary_files,
+            scan_depth: self.ctx.config.scan_depth,
+            refresh,
+        };
+        let files = match file_finder.find_file_metas().await {
+            Ok(files) => files,
+            // The first file of the symbol may only be written while tailing
+            Err(err) if err.is::<SymbolNotFound>() => Vec::new(),
+            Err(err) => return Err(err),
+        };
+
+        for file_meta in files {
+            if self.completed.contains(&file_meta.path) {
+                continue;
+            }
+
+            let rows_read = self.rows_read.entry(file_meta.path.clone()).or_insert(0);
+            let _open_file = acquire_open_file(&self.ctx).await?;
+            let entry_stream = stream_parquet_file(&self.ctx, &file_meta.path, self.batch_size).await
+                .map_err(|_| anyhow::anyhow!("Failed to stream parquet file"))?;
+            let mut entry_stream = Box::pin(entry_stream.skip(*rows_read));
+
+            while let Some(entry) = entry_stream.next().await {
+                let entry = entry.map_err(|e| anyhow::anyhow!("Error reading entry: {:?}", e))?;
+                *rows_read += 1;
+
+                if !time_filter.contains_entry(&entry) {
+                    continue;
+                }
+                // Messages that fail to decode are logged and skipped
+                let Ok(mut message) = decode_entry(entry, &self.symbol, self.base64_fallback) else {
+                    continue;
+                };
+                if self.include_meta {
+                    message = message.with_meta(&self.exchange, &self.market_type, &self.stream);
+                }
+                let message = ProjectedMessage::new(message, FieldSelection::all(), &self.ctx.config);
+                socket.send(WsMessage::Text(serde_json::to_string(&message)?.into())).await?;
+            }
+
+            if file_meta.date < live_since {
+                self.completed.insert(file_meta.path);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Waits for the poll interval while answering the client, returns `false` if the client went away.
+async fn wait_unless_closed(socket: &mut WebSocket, poll_interval: Duration) -> bool {
+    let sleep = tokio::time::sleep(poll_interval);
+    tokio::pin!(sleep);
+
+    loop {
+        tokio::select! {
+            _ = &mut sleep => return true,
+            received = socket.recv() => match received {
+                Some(Ok(WsMessage::Close(_))) | Some(Err(_)) | None => return false,
+                // Other client messages are ignored, pings are answered by axum
+                Some(Ok(_)) => continue,
+            },
+        }
+    }
+}
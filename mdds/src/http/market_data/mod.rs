@@ -1,16 +1,61 @@
-use crate::http::ApiContext;
-use axum::extract::{Path, Query};
-use axum::routing::get;
+mod batch;
+mod candle;
+mod count;
+mod debug;
+mod download;
+mod export;
+mod fields;
+mod format;
+mod live;
+pub mod preload;
+mod range;
+mod replay;
+mod schema;
+mod symbols;
+mod tick;
+
+use crate::codec::{self, DataEncoding, Message};
+use crate::config::{Config, ResponseLimitMode};
+use anyhow::Context;
+use crate::http::{request_id, ApiContext, Error};
+use axum::body::Body;
+use axum::extract::{Path, Query, RawQuery};
+use axum::routing::{get, post};
 use axum::{Extension, Json, Router};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, SecondsFormat, TimeDelta, TimeZone, Utc};
+use chrono_tz::Tz;
 use http::{HeaderMap, StatusCode};
-use serde::{Deserialize, Serialize};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::convert::Infallible;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use axum::response::sse::{Event, KeepAlive, Sse};
 use axum::response::{IntoResponse, Response};
 use axum_streams::StreamBodyAs;
-use futures::{stream, Stream, StreamExt};
+use futures::future::BoxFuture;
+use futures::stream::BoxStream;
+use futures::{future, stream, Stream, StreamExt};
 use crate::fs::file_finder::FileFinder;
-use crate::fs::TimeSlice;
+use crate::fs::symbol_scanner::{is_glob, SymbolScanner};
+use crate::fs::column_names::{self, ColumnNames};
+use crate::fs::{compression, parquet_meta, FileMetadata, StreamNotFound, SymbolNotFound, TimeSlice};
+use crate::http::metrics::{ActiveStreamGuard, DECODE_ERRORS, FIND_FILES_DURATION, HTTP_REQUESTS, PARQUET_FILES_OPENED, PARQUET_FILES_PRUNED, STREAMED_BYTES};
+use std::future::Future;
+use std::io::ErrorKind;
+use std::ops::Range;
+use sha2::{Digest, Sha256};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+use tokio::sync::OwnedSemaphorePermit;
+use candle::{CandleFields, CandleResponse};
+use fields::{FieldSelection, ProjectedMessage};
+use tick::{ParsedMessage, ParsedOrRawMessage};
+use format::ResponseFormat;
+use http::header::{ACCEPT, CACHE_CONTROL, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use http::HeaderValue;
+use utoipa::{IntoParams, OpenApi, ToSchema};
 
 pub fn router() -> Router {
 
@@ -18,6 +63,8 @@ pub fn router() -> Router {
     let api_version = "v1";
     let stream_path = "/stream";
     let stream_version = "v1";
+    let ws_path = "/ws";
+    let ws_version = "v1";
     let market_data_path = "market-data";
     let exchange_capture_path = "{exchange}";
     let market_type_path = "{market_type}";
@@ -32,6 +79,26 @@ pub fn router() -> Router {
         + "/" + stream_capture_path
         + "/" + symbol_capture_path;
 
+    let count_route = api_route.clone() + "/count";
+    let schema_route = api_route.clone() + "/schema";
+    let range_route = api_route.clone() + "/range";
+    let download_route = api_route.clone() + "/download";
+    let export_route = api_route.clone() + "/export";
+
+    let symbols_route = api_path.to_string()
+        + "/" + api_version
+        + "/" + market_data_path
+        + "/" + exchange_capture_path
+        + "/" + market_type_path
+        + "/" + stream_capture_path;
+
+    let freshness_route = symbols_route.clone() + "/freshness";
+
+    let batch_route = api_path.to_string()
+        + "/" + api_version
+        + "/" + market_data_path
+        + "/query";
+
     let stream_route = stream_path.to_string()
         + "/" + stream_version
         + "/" + market_data_path
@@ -40,63 +107,585 @@ pub fn router() -> Router {
         + "/" + stream_capture_path
         + "/" + symbol_capture_path;
 
+    let replay_route = stream_path.to_string()
+        + "/" + stream_version
+        + "/" + market_data_path
+        + "/replay";
+
+    let ws_route = ws_path.to_string()
+        + "/" + ws_version
+        + "/" + market_data_path
+        + "/" + exchange_capture_path
+        + "/" + market_type_path
+        + "/" + stream_capture_path
+        + "/" + symbol_capture_path;
+
     // Example URLs:
     // localhost:8080/api/v1/market-data/binance/spot/trade/ethusdt?from=2025-10-15T16:21:30.160Z&to=2025-10-15T16:21:39.049Z
     // localhost:8080/api/v1/market-data/binance/spot/trade/ethusdt?from=2025-10-15T16:21:32.000Z&to=2025-10-15T16:21:32.100Z
 
+    // localhost:8080/api/v1/market-data/binance/spot/trade/ethusdt/count?from=2025-10-15T16:00:00.000Z&to=2025-10-15T17:00:00.000Z
+    // localhost:8080/api/v1/market-data/binance/spot/trade/ethusdt/schema
+    // localhost:8080/api/v1/market-data/binance/spot/trade/ethusdt/range
+    // localhost:8080/api/v1/market-data/binance/spot/trade/ethusdt/download?from=2025-10-14T00:00:00.000Z&to=2025-10-16T00:00:00.000Z
+    // localhost:8080/api/v1/market-data/binance/spot/trade/ethusdt/export?from=2025-10-14T12:00:00.000Z&to=2025-10-16T12:00:00.000Z
+    // localhost:8080/api/v1/market-data/binance/spot/trade
+    // localhost:8080/api/v1/market-data/binance/spot/trade/freshness
+    // POST localhost:8080/api/v1/market-data/query
+
     // localhost:8080/stream/v1/market-data/binance/spot/trade/ethusdt?from=2025-10-15T16:21:30.160Z&to=2025-10-15T16:21:39.049Z
     // localhost:8080/stream/v1/market-data/binance/spot/trade/ethusdt?from=2025-10-15T16:21:32.000Z&to=2025-10-15T16:21:32.100Z
 
+    // POST localhost:8080/stream/v1/market-data/replay
+
+    // ws://localhost:8080/ws/v1/market-data/binance/spot/trade/ethusdt?from=2025-10-15T16:00:00.000Z&to=2025-10-15T18:00:00.000Z
+
     // Example data file paths:
     // data/market_data/binance/spot/trade/ethusdt.2019-04-05.parquet
     // data/market_data/binance/spot/trade/ethusdt.2019-04-06.parquet
     Router::new()
         .route(api_route.as_str(),get(get_market_data))
+        .route(count_route.as_str(),get(count::count_market_data))
+        .route(schema_route.as_str(),get(schema::get_schema))
+        .route(range_route.as_str(),get(range::get_time_range))
+        .route(download_route.as_str(),get(download::download_market_data))
+        .route(export_route.as_str(),get(export::export_market_data))
+        .route(symbols_route.as_str(),get(symbols::list_symbols))
+        .route(freshness_route.as_str(),get(symbols::symbol_freshness))
+        .route(batch_route.as_str(),post(batch::query_market_data))
         .route(stream_route.as_str(),get(stream_market_data))
+        .route(replay_route.as_str(),post(replay::replay_market_data))
+        .route(ws_route.as_str(),get(live::ws_market_data))
+}
+
+/// The diagnostics routes, only merged with `debug_endpoints` as they expose the server's paths.
+pub fn debug_router() -> Router {
+    // Example URL:
+    // localhost:8080/debug/echo/binance/spot/trade/ethusdt?from=2025-10-15T16:00:00.000Z&to=2025-10-15T17:00:00.000Z
+    Router::new().route("/debug/echo/{exchange}/{market_type}/{stream}/{symbol}", get(debug::echo))
 }
 
-#[derive(Deserialize)]
+/// The OpenAPI description of the market data routes, generated from the handler annotations.
+#[derive(OpenApi)]
+#[openapi(
+    paths(get_market_data, stream_market_data),
+    tags((name = "market-data", description = "Historical market data read from parquet files")),
+)]
+pub struct ApiDoc;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 struct QueryParams {
+    /// The start of the time range, RFC 3339 or a local time without offset interpreted in `tz`.
+    #[serde(default, rename = "from", deserialize_with = "deserialize_from")]
+    #[param(value_type = Option<String>)]
+    from_param: Option<TimeParam>,
+    /// The end of the time range, RFC 3339 or a local time without offset interpreted in `tz`.
+    #[serde(default, rename = "to", deserialize_with = "deserialize_to")]
+    #[param(value_type = Option<String>)]
+    to_param: Option<TimeParam>,
+    /// The UTC start of the time range, resolved by `resolve_time_range`.
+    #[serde(skip)]
     from: Option<DateTime<Utc>>,
+    /// The UTC end of the time range, resolved by `resolve_time_range`.
+    #[serde(skip)]
     to: Option<DateTime<Utc>>,
+    /// The IANA timezone of `from`/`to` values without offset, e.g. `America/New_York`, UTC if absent.
+    tz: Option<String>,
+    /// Query from the start of the current day in `tz` up to now, instead of `from`/`to`.
+    #[serde(default)]
+    today: bool,
+    /// Query the time window up to now, e.g. `15m` or `1h`, instead of `from`/`to`.
+    window: Option<String>,
+    /// Alternative to `from` in epoch milliseconds, like the returned `timestamp_millis`.
+    from_millis: Option<i64>,
+    /// Alternative to `to` in epoch milliseconds, like the returned `timestamp_millis`.
+    to_millis: Option<i64>,
+    /// Maximum number of messages to return in a single page.
+    limit: Option<usize>,
+    /// Number of messages to skip before the page starts.
+    offset: Option<usize>,
+    /// Resume from the first message with `timestamp_millis >= cursor`, as returned in `next_cursor`.
+    cursor: Option<i64>,
+    /// The time order in which streamed messages are emitted.
+    #[serde(default)]
+    order: Order,
+    /// The framing of streamed JSON messages, one object per line by default or a single `array`.
+    #[serde(default)]
+    format: JsonFraming,
+    /// Bypass the cached directory scan, e.g. when new files landed mid-session.
+    #[serde(default)]
+    refresh: bool,
+    /// Aggregate the messages into OHLCV candles of this interval, e.g. `1s`, `1m` or `1h`.
+    interval: Option<String>,
+    /// Fail with `422 Unprocessable Entity` if any date of the time range has no market data file,
+    /// and with the error of a file that fails to read instead of skipping it.
+    #[serde(default)]
+    strict: bool,
+    /// Include the exchange, market type and stream in each message.
+    #[serde(default)]
+    include_meta: bool,
+    /// Skip candles of intervals without any trades instead of carrying the previous close forward.
+    #[serde(default)]
+    skip_empty: bool,
+    /// Include messages exactly at `to`, by default the time range ends right before it.
+    #[serde(default)]
+    inclusive_end: bool,
+    /// Drop duplicate messages with the same `timestamp_millis`, `timestamp_sub_sec` and `data`.
+    #[serde(default)]
+    dedup: bool,
+    /// Skip messages that fail to decode while streaming, instead of sending an error for them.
+    #[serde(default)]
+    skip_errors: bool,
+    /// Return the files the query would read instead of reading them.
+    #[serde(default)]
+    dry_run: bool,
+    /// The number of rows per record batch read from the parquet files, overrides `parquet_reader_record_batch_size`.
+    batch_size: Option<usize>,
+    /// Buffer streamed messages into writes of this many messages, flushed after `flush_ms` at the latest.
+    flush_count: Option<usize>,
+    /// Flush buffered messages at the latest this many milliseconds after the first one was buffered.
+    flush_ms: Option<u64>,
+    /// Return the newest `last` messages newest first, ignoring `from`/`to`, e.g. the last 100 trades.
+    last: Option<usize>,
+    /// Stream the messages at the pace they originally occurred at, multiplied by this factor,
+    /// e.g. `1` for real time or `10` for 10x faster. `0` or absent streams as fast as possible.
+    speed: Option<f64>,
+    /// Pass data that isn't valid UTF-8 base64 encoded with `base64` instead of dropping it with `utf8`.
+    encoding: Option<DataEncoding>,
+    /// Comma-separated message fields to include in JSON responses, e.g. `timestamp_millis,data`.
+    fields: Option<String>,
+    /// Embed `data` that is valid JSON as nested value in JSON responses, instead of as escaped string.
+    #[serde(default)]
+    raw_json: bool,
+    /// Comma-separated additional parquet columns to include in each message as `columns`, e.g. `side,quantity`.
+    columns: Option<String>,
+    /// Return the parsed fields of known exchange payloads, e.g. `price` and `quantity` of Binance trades,
+    /// instead of the raw `data`.
+    #[serde(default)]
+    parse: bool,
+    /// Fail on payloads that don't parse with `parse`, instead of returning them raw.
+    #[serde(default)]
+    parse_strict: bool,
+    /// The index of the first row of the day's file, instead of filtering by timestamp.
+    /// Requires `from`/`to` of a single day.
+    row_from: Option<usize>,
+    /// The index right after the last row of the day's file, e.g. `row_from=1000&row_to=2000` for 1000 rows.
+    row_to: Option<usize>,
+    /// End a streamed JSON response with a `{"_summary": {...}}` object, which tells a completed stream
+    /// from a dropped connection.
+    #[serde(default)]
+    summary: bool,
 }
 
-// TODO: Move this to a separate codec repo to share with adapters and s9_parquet
-#[derive(Debug, Serialize)]
-struct Message {
-    pub timestamp_millis: i64,
-    pub timestamp_sec: i64,
-    pub timestamp_sub_sec: i32,
-    data: String,
+impl QueryParams {
+
+    /// Resolves the UTC `from`/`to` of the `from`/`to` parameters in `tz`, or of `from_millis`/`to_millis`,
+    /// rejecting a bound given in both forms. `today` and `window` end now instead.
+    ///
+    /// As the files are named by their UTC date, the converted bounds also select the right files near local midnight.
+    fn resolve_time_range(&mut self) -> Result<(), String> {
+        let tz = match self.tz.as_deref() {
+            Some(tz) => tz.parse::<Tz>()
+                .map_err(|_| format!("invalid tz {:?}, expected an IANA timezone like America/New_York", tz))?,
+            None => Tz::UTC,
+        };
+        self.from = resolve_bound("from", self.from_param, self.from_millis, tz)?;
+        self.to = resolve_bound("to", self.to_param, self.to_millis, tz)?;
+
+        if !self.today && self.window.is_none() {
+            return Ok(());
+        }
+        if self.from.is_some() || self.to.is_some() {
+            return Err("today and window can't be combined with from/to".to_string());
+        }
+        let now = Utc::now();
+        let from = match (self.today, self.window.as_deref()) {
+            (true, Some(_)) => return Err("only one of today and window may be given".to_string()),
+            (true, None) => {
+                let midnight = now.with_timezone(&tz).date_naive().and_time(NaiveTime::MIN);
+                tz.from_local_datetime(&midnight)
+                    .earliest()
+                    .map(|midnight| midnight.with_timezone(&Utc))
+                    .ok_or_else(|| format!("the start of today does not exist in {}", tz))?
+            }
+            (false, Some(window)) => {
+                let window_millis = candle::parse_interval_millis(window)
+                    .ok_or_else(|| "window must be like 30s, 15m, 1h or 1d".to_string())?;
+                now - TimeDelta::milliseconds(window_millis)
+            }
+            (false, None) => unreachable!("checked above"),
+        };
+        self.from = Some(from);
+        self.to = Some(now);
+        Ok(())
+    }
+
+    /// The additional parquet columns requested with `columns`, none if not given.
+    fn extra_columns(&self) -> Vec<String> {
+        self.columns
+            .iter()
+            .flat_map(|columns| columns.split(','))
+            .map(str::trim)
+            .filter(|column| !column.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// The rows of the day's file selected with `row_from`/`row_to`, `None` to filter by timestamp instead.
+    ///
+    /// Row indices only identify messages within a file, so `from`/`to` must lie within a single day.
+    fn row_range(&self) -> Result<Option<Range<usize>>, String> {
+        let rows = match (self.row_from, self.row_to) {
+            (None, None) => return Ok(None),
+            (row_from, row_to) => row_from.unwrap_or(0)..row_to.unwrap_or(usize::MAX),
+        };
+        if rows.is_empty() {
+            return Err("row_from must be less than row_to".to_string());
+        }
+        let Some((from, to)) = self.from.zip(self.to) else {
+            return Err("row_from/row_to require from and to".to_string());
+        };
+        // An exclusive end at midnight still ends the day before
+        let last = if self.inclusive_end { to } else { to - TimeDelta::nanoseconds(1) };
+        if from.date_naive() != last.date_naive() {
+            return Err("row_from/row_to require from and to within a single day".to_string());
+        }
+        Ok(Some(rows))
+    }
+
+    /// The message fields selected with `fields`, all of them if not given.
+    fn field_selection(&self) -> Result<FieldSelection, String> {
+        match self.fields.as_deref() {
+            Some(fields) => FieldSelection::parse(fields),
+            None => Ok(FieldSelection::all()),
+        }
+    }
+
+    /// Takes `batch_size` if within `1..=max_record_batch_size`, `parquet_reader_record_batch_size` if absent.
+    fn record_batch_size(&self, config: &Config) -> Result<usize, String> {
+        match self.batch_size {
+            None => Ok(config.parquet_reader_record_batch_size),
+            Some(batch_size) if (1..=config.max_record_batch_size).contains(&batch_size) => Ok(batch_size),
+            Some(_) => Err(format!("batch_size must be between 1 and {}", config.max_record_batch_size)),
+        }
+    }
+
+    /// The number of messages and time after which buffered messages are flushed, `None` to write each message.
+    fn flush(&self) -> Result<Option<(usize, Duration)>, String> {
+        if self.flush_count == Some(0) || self.flush_ms == Some(0) {
+            return Err("flush_count and flush_ms must be positive".to_string());
+        }
+        let flush_ms = match self.flush_ms {
+            Some(flush_ms) if flush_ms > MAX_FLUSH_MILLIS => return Err(format!("flush_ms must not exceed {}", MAX_FLUSH_MILLIS)),
+            Some(flush_ms) => flush_ms,
+            None if self.flush_count.is_some() => DEFAULT_FLUSH_MILLIS,
+            None => return Ok(None),
+        };
+        Ok(Some((self.flush_count.unwrap_or(usize::MAX), Duration::from_millis(flush_ms))))
+    }
+
+    /// Whether data that isn't valid UTF-8 is base64 encoded rather than dropped, by `encoding` or the configured default.
+    fn base64_fallback(&self, config: &Config) -> bool {
+        match self.encoding {
+            Some(encoding) => encoding == DataEncoding::Base64,
+            None => config.base64_fallback,
+        }
+    }
 }
 
-#[derive(Debug, Serialize)]
+/// A time query parameter as given, local times are converted to UTC once `tz` is known.
+#[derive(Clone, Copy, Debug)]
+enum TimeParam {
+    Utc(DateTime<Utc>),
+    Local(NaiveDateTime),
+}
+
+fn deserialize_from<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<TimeParam>, D::Error> {
+    deserialize_time_param("from", deserializer)
+}
+
+fn deserialize_to<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<TimeParam>, D::Error> {
+    deserialize_time_param("to", deserializer)
+}
+
+/// Parses a time query parameter, naming the parameter and the expected format if it is malformed,
+/// as the query rejection of axum would only pass on the bare parse error.
+fn deserialize_time_param<'de, D: Deserializer<'de>>(name: &str, deserializer: D) -> Result<Option<TimeParam>, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    let err = match value.parse::<DateTime<Utc>>() {
+        Ok(time) => return Ok(Some(TimeParam::Utc(time))),
+        Err(err) => err,
+    };
+    // Without offset, e.g. 2025-10-15T12:21:30.160 or 2025-10-15, the time is local to `tz`
+    if let Ok(time) = value.parse::<NaiveDateTime>() {
+        return Ok(Some(TimeParam::Local(time)));
+    }
+    if let Ok(date) = value.parse::<NaiveDate>() {
+        return Ok(Some(TimeParam::Local(date.and_time(NaiveTime::MIN))));
+    }
+    Err(D::Error::custom(format!(
+        "invalid `{}` value {:?} ({}), expected RFC 3339 like 2025-10-15T16:21:30.160Z or a local time like 2025-10-15T12:21:30.160",
+        name, value, err
+    )))
+}
+
+fn resolve_bound(name: &str, time: Option<TimeParam>, millis: Option<i64>, tz: Tz) -> Result<Option<DateTime<Utc>>, String> {
+    match (time, millis) {
+        (Some(_), Some(_)) => Err(format!("only one of {} and {}_millis may be given", name, name)),
+        (None, Some(millis)) => DateTime::<Utc>::from_timestamp_millis(millis)
+            .map(Some)
+            .ok_or_else(|| format!("{}_millis is out of range", name)),
+        (Some(TimeParam::Utc(time)), None) => Ok(Some(time)),
+        // A local time skipped by a daylight saving transition doesn't exist, a repeated one is taken at its first occurrence
+        (Some(TimeParam::Local(time)), None) => tz.from_local_datetime(&time)
+            .earliest()
+            .map(|time| Some(time.with_timezone(&Utc)))
+            .ok_or_else(|| format!("{} {} does not exist in {}", name, time, tz)),
+        (None, None) => Ok(None),
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+enum Order {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// How the streaming endpoint frames JSON messages.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+enum JsonFraming {
+    /// Newline-delimited JSON, one object per line.
+    #[default]
+    Lines,
+    /// A single JSON array, for clients that can't parse newline-delimited JSON.
+    #[serde(alias = "json_array")]
+    Array,
+}
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+type EntryResult = Result<s9_parquet::Entry, BoxError>;
+
+#[derive(Debug, Serialize, ToSchema)]
 struct ApiResponse<M> {
     messages: M,
+    /// The number of files matching the symbol and time range, messages are empty either without
+    /// files or if the files hold no messages of the time range.
+    file_count: usize,
+    /// The `cursor` to request the next page with, if more messages are available.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_cursor: Option<i64>,
+    /// The dates within the queried time range without any market data file.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    missing_dates: Vec<NaiveDate>,
+}
+
+/// A streamed JSON message, or the summary after the last one with `summary`.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum StreamFrame {
+    Message(ProjectedMessage),
+    Summary {
+        #[serde(rename = "_summary")]
+        summary: StreamSummary,
+    },
+}
+
+/// What a completed stream has sent, so clients can verify they received all of it.
+#[derive(Debug, Serialize)]
+struct StreamSummary {
+    /// The number of messages sent.
+    count: usize,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    /// The number of files the messages were read from.
+    files: usize,
+}
+
+/// The files a query would read, returned instead of the messages with `dry_run`.
+#[derive(Debug, Serialize)]
+struct DryRunResponse {
+    files: Vec<DryRunFile>,
+    /// The dates within the queried time range without any market data file.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    missing_dates: Vec<NaiveDate>,
 }
 
+#[derive(Debug, Serialize)]
+struct DryRunFile {
+    path: PathBuf,
+    stream: String,
+    symbol: String,
+    date: NaiveDate,
+}
+
+#[utoipa::path(
+    get,
+    path = "/stream/v1/market-data/{exchange}/{market_type}/{stream}/{symbol}",
+    tag = "market-data",
+    params(
+        ("exchange" = String, Path, description = "The exchange, e.g. `binance`"),
+        ("market_type" = String, Path, description = "The market type, e.g. `spot`"),
+        ("stream" = String, Path, description = "The stream, e.g. `trade`"),
+        ("symbol" = String, Path, description = "The symbol, e.g. `ethusdt`"),
+        QueryParams,
+    ),
+    responses(
+        (status = 200, description = "The messages of the time range, one JSON object per line, or CSV, Arrow or Server-Sent Events by `Accept`", body = Message),
+        (status = 400, description = "Invalid path or query parameters"),
+    )
+)]
 async fn stream_market_data(
     ctx: Extension<ApiContext>,
     Path((exchange, market_type, stream, symbol)): Path<(String, String, String, String)>,
-    Query(query): Query<QueryParams>,
+    Query(mut query): Query<QueryParams>,
+    headers: HeaderMap,
 ) -> impl IntoResponse
 {
+    metrics::counter!(HTTP_REQUESTS, "route" => "/stream/v1/market-data").increment(1);
+    let symbol = ctx.symbol_aliases.resolve(&symbol).to_string();
+
     // Validate parameters first
-    if query.from.is_none() || query.to.is_none() {
-        return (StatusCode::BAD_REQUEST, "Missing from/to parameters").into_response();
+    if let Err(message) = query.resolve_time_range() {
+        return Error::BadRequest(message).into_response();
+    }
+    if let Err(message) = validate_stream_path(&ctx.config, &exchange, &market_type, &stream)
+        .and_then(|_| validate_path_segment(&symbol)) {
+        return Error::BadRequest(message).into_response();
+    }
+    let Some((from, to)) = query.from.zip(query.to) else {
+        return Error::BadRequest("Missing from/to parameters".to_string()).into_response();
+    };
+    if query.speed.is_some_and(|speed| !speed.is_finite() || (speed != 0.0 && speed < MIN_REPLAY_SPEED)) {
+        return Error::BadRequest(format!("speed must be 0 or at least {}", MIN_REPLAY_SPEED)).into_response();
+    }
+    if let Err(message) = validate_time_range(&from, &to, ctx.config.max_query_range_days) {
+        return Error::BadRequest(message).into_response();
+    }
+    let fields = match query.field_selection() {
+        Ok(fields) => fields,
+        Err(message) => return Error::BadRequest(message).into_response(),
+    };
+    let raw_json = query.raw_json;
+    let batch_size = match query.record_batch_size(&ctx.config) {
+        Ok(batch_size) => batch_size,
+        Err(message) => return Error::BadRequest(message).into_response(),
+    };
+
+    let flush = match query.flush() {
+        Ok(flush) => flush,
+        Err(message) => return Error::BadRequest(message).into_response(),
+    };
+    if let Err(message) = query.row_range() {
+        return Error::BadRequest(message).into_response();
+    }
+    if open_files_exhausted(&ctx) {
+        tracing::warn!("Rejecting stream, all files of max_open_files are in use");
+        return Error::Overloaded.into_response();
     }
 
-    let stream = s_market_data(ctx, Path((exchange, market_type, stream, symbol)), Query(query)).await;
-    let stream = stream.map(|result| result.map_err(|e| crate::http::Error::Anyhow(e)));
+    let response_format = ResponseFormat::from_headers(&headers);
+    let active_streams = Arc::clone(&ctx.active_streams);
+    let path = Path((exchange, market_type, stream, symbol));
 
-    Response::builder()
+    let response = Response::builder()
         .status(200)
-        .header("content-type", "application/json")
         .header("cache-control", "no-cache")
         .header("connection", "keep-alive")
         .header("x-accel-buffering", "no") // Disable nginx buffering if behind nginx
-        .body(StreamBodyAs::json_nl_with_errors(stream))
-        .unwrap().into_response()
+        .header("x-query-from", from.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+        .header("x-query-to", to.to_rfc3339_opts(SecondsFormat::AutoSi, true));
+
+    let response = match response_format {
+        ResponseFormat::Json => {
+            let config = ctx.config.clone();
+            let framing = query.format;
+            let summary = query.summary;
+            let (file_count, stream) = s_market_data(ctx, path, Query(query), OpenFiles::Reserve).await;
+            let count = Arc::new(AtomicUsize::new(0));
+            let message_count = Arc::clone(&count);
+            let stream = stream.map(move |result| result
+                .map(|message| {
+                    message_count.fetch_add(1, Ordering::Relaxed);
+                    StreamFrame::Message(ProjectedMessage::new(message, fields, &config).with_raw_json(raw_json))
+                })
+                .map_err(|e| crate::http::Error::Anyhow(e)));
+            // Only reached once all messages were sent, an error ends the body before
+            let summary_frame = stream::once(async move {
+                Ok(StreamFrame::Summary {
+                    summary: StreamSummary { count: count.load(Ordering::Relaxed), from, to, files: file_count },
+                })
+            });
+            let stream = if summary { stream.chain(summary_frame).boxed() } else { stream.boxed() };
+            // The array is still streamed element by element, e.g. `[]` without any message
+            let body = match framing {
+                JsonFraming::Lines => StreamBodyAs::json_nl_with_errors(stream),
+                JsonFraming::Array => StreamBodyAs::json_array_with_errors(stream),
+            };
+            response
+                .header("content-type", "application/json")
+                .body(body)
+                .unwrap().into_response()
+        }
+        ResponseFormat::Csv => {
+            let (_, stream) = s_market_data(ctx, path, Query(query), OpenFiles::Reserve).await;
+            response
+                .header("content-type", "text/csv")
+                .body(format::csv_body_stream(stream))
+                .unwrap().into_response()
+        }
+        ResponseFormat::EventStream => {
+            let config = ctx.config.clone();
+            let request_id = request_id(&headers).to_string();
+            let (_, stream) = s_market_data(ctx, path, Query(query), OpenFiles::Reserve).await;
+            // Errors are sent as `error` events, so clients can tell them from the end of the stream,
+            // with the request ID to find them in the logs
+            let error_event = move |err: String| Event::default()
+                .event("error")
+                .data(format!("{} (request ID {})", err, request_id));
+            let events = stream.map(move |result| {
+                let event = match result {
+                    Ok(message) => Event::default()
+                        .json_data(ProjectedMessage::new(message, fields, &config).with_raw_json(raw_json))
+                        .unwrap_or_else(|err| error_event(err.to_string())),
+                    Err(err) => error_event(err.to_string()),
+                };
+                Ok::<_, Infallible>(event)
+            });
+            let sse = Sse::new(events).keep_alive(KeepAlive::default());
+            response
+                .header("content-type", mime::TEXT_EVENT_STREAM.as_ref())
+                .body(sse.into_response().into_body())
+                .unwrap().into_response()
+        }
+        ResponseFormat::Arrow => {
+            // Arrow carries the data as binary column, so entries are streamed without decoding them
+            let (_, entries) = s_market_entries(ctx, path, Query(query), OpenFiles::Reserve).await;
+            response
+                .header("content-type", format::ARROW_STREAM_CONTENT_TYPE)
+                .body(format::arrow_body_stream(entries, batch_size))
+                .unwrap().into_response()
+        }
+    };
+
+    let response = match flush {
+        Some((flush_count, flush_interval)) => response.map(|body| format::buffered_body(body, flush_count, flush_interval)),
+        None => response,
+    };
+
+    // The stream is active until its body is dropped, i.e. sent completely or the client disconnected
+    let guard = ActiveStreamGuard::new(&active_streams);
+    response.map(move |body| {
+        let chunks = body.into_data_stream().map(move |chunk| {
+            let _guard = &guard;
+            chunk
+        });
+        Body::from_stream(chunks)
+    })
 }
 
 
@@ -104,15 +693,55 @@ async fn s_market_data(
     ctx: Extension<ApiContext>,
     Path((exchange, market_type, stream, symbol)): Path<(String, String, String, String)>,
     Query(query): Query<QueryParams>,
-) -> impl Stream<Item = Result<Message, anyhow::Error>>
+    open_files: OpenFiles,
+) -> (usize, impl Stream<Item = Result<Message, anyhow::Error>>)
+{
+    let include_meta = query.include_meta;
+    let skip_errors = query.skip_errors;
+    let base64_fallback = query.base64_fallback(&ctx.config);
+    let path = Path((exchange.clone(), market_type.clone(), stream.clone(), symbol.clone()));
+    let (file_count, entries) = s_market_entries(ctx, path, Query(query), open_files).await;
+    let messages = entries
+        .filter_map(move |result| {
+            let message = match result.map(|entry| decode_entry(entry, &symbol, base64_fallback)) {
+                Ok(Ok(message)) => message,
+                // Already logged and counted by `decode_entry`, errors reading the files are never skipped
+                Ok(Err(_)) if skip_errors => return future::ready(None),
+                Ok(Err(err)) | Err(err) => return future::ready(Some(Err(err))),
+            };
+            if include_meta {
+                future::ready(Some(Ok(message.with_meta(&exchange, &market_type, &stream))))
+            } else {
+                future::ready(Some(Ok(message)))
+            }
+        });
+    (file_count, messages)
+}
+
+
+async fn s_market_entries(
+    ctx: Extension<ApiContext>,
+    Path((exchange, market_type, stream, symbol)): Path<(String, String, String, String)>,
+    Query(query): Query<QueryParams>,
+    open_files: OpenFiles,
+) -> (usize, BoxStream<'static, Result<s9_parquet::Entry, anyhow::Error>>)
 {
     tracing::info!("loading stream market data for {}/{}/{}/{}", exchange, market_type, stream, symbol);
 
-    let file_paths = match query.from.zip(query.to) {
+    let mut files = match query.from.zip(query.to) {
         Some((from, to)) => {
+            if let Err(message) = validate_time_range(&from, &to, ctx.config.max_query_range_days) {
+                return (0, stream::once(async move { Err(anyhow::anyhow!(message)) }).boxed());
+            }
+
             let file_finder = FileFinder {
                 parquet_file_extension: &ctx.config.parquet_file_extension,
+                date_format: &ctx.config.filename_date_format,
+                separator: &ctx.config.filename_separator,
+                layout: ctx.config.directory_layout,
                 base_path: &ctx.config.market_data_path,
+                archive_paths: &ctx.config.archive_data_paths,
+                remote_store: ctx.remote_store.as_deref(),
                 exchange: &exchange,
                 market_type: &market_type,
                 stream: &stream,
@@ -120,175 +749,1502 @@ async fn s_market_data(
                 time_slice: &TimeSlice {
                     from: &from,
                     to: &to,
+                    inclusive_end: query.inclusive_end,
                 },
+                scan_cache: &ctx.scan_cache,
+                include_boundary_files: ctx.config.include_boundary_files,
+                scan_depth: ctx.config.scan_depth,
+                refresh: query.refresh,
             };
 
-            match file_finder.find_files().await {
-                Ok(paths) => paths,
-                Err(e) => return stream::once(async move { Err(anyhow::anyhow!("Failed to find files: {}", e)) }).boxed(),
+            let rows = match query.row_range() {
+                Ok(rows) => rows,
+                Err(message) => return (0, stream::once(async move { Err(anyhow::anyhow!(message)) }).boxed()),
+            };
+            match find_files(&file_finder).await {
+                // Rows are sliced from the day's file only, not from boundary files
+                Ok(files) => files.into_iter()
+                    .filter(|file_meta| rows.is_none() || file_meta.date == from.date_naive())
+                    .collect::<Vec<_>>(),
+                Err(e) if e.is::<SymbolNotFound>() => return (0, stream::once(async move { Err(e) }).boxed()),
+                Err(e) => return (0, stream::once(async move { Err(anyhow::anyhow!("Failed to find files: {}", e)) }).boxed()),
             }
         }
-        None => return stream::once(async move { Err(anyhow::anyhow!("Missing from/to parameters")) }).boxed(),
+        None => return (0, stream::once(async move { Err(anyhow::anyhow!("Missing from/to parameters")) }).boxed()),
     };
 
-    let from = query.from.unwrap();
-    let to = query.to.unwrap();
+    let batch_size = match query.record_batch_size(&ctx.config) {
+        Ok(batch_size) => batch_size,
+        Err(message) => return (0, stream::once(async move { Err(anyhow::anyhow!(message)) }).boxed()),
+    };
+    let time_filter = TimeFilter::new(query.from.unwrap(), query.to.unwrap(), query.inclusive_end);
+    let rows = query.row_range().ok().flatten();
+    let by_rows = rows.is_some();
+    let order = query.order;
 
-    // Create streams for all files and merge them
-    let file_streams: Vec<_> = file_paths.into_iter().map(|file_path| {
-        let batch_size = ctx.config.parquet_reader_record_batch_size;
-        async move {
-            match stream_parquet_file(batch_size, &file_path).await {
+    if order == Order::Desc {
+        files.reverse();
+    }
+
+    // All files the merge opens at once are reserved up front, a stream waiting for more files while holding
+    // some could deadlock with other streams doing the same
+    let open_files = match open_files {
+        OpenFiles::Reserve => match acquire_open_files(&ctx, merged_open_files(&files)).await {
+            Ok(open_files) => Some(open_files),
+            Err(err) => {
+                tracing::error!("Error reading parquet file: {}", err);
+                return (0, stream::once(async move { Err(anyhow::Error::from(Error::Overloaded)) }).boxed());
+            }
+        },
+        OpenFiles::Reserved => None,
+    };
+
+    // Create the streams of all files, which are only opened once merged
+    let file_count = files.len();
+    let file_streams: Vec<_> = files.into_iter().map(|file_meta| {
+        let FileMetadata { path: file_path, date } = file_meta;
+        let ctx = ctx.clone();
+        let rows = rows.clone();
+        let max_buffered_entries = batch_size * ctx.config.descending_buffer_max_batches;
+        let open = async move {
+            if !by_rows && !file_may_contain(&ctx.config, &file_path, &time_filter).await {
+                return stream::empty().boxed();
+            }
+            match stream_parquet_file(&ctx, &file_path, batch_size).await {
                 Ok(entry_stream) => {
+                    // Sliced in the order of the file, before a descending order reverses it
+                    let entry_stream = match rows {
+                        Some(rows) => entry_stream.skip(rows.start).take(rows.len()).boxed(),
+                        None => entry_stream.boxed(),
+                    };
+                    let entry_stream = match order {
+                        Order::Asc => entry_stream.boxed(),
+                        Order::Desc => reverse_entry_stream(entry_stream, max_buffered_entries).boxed(),
+                    };
+
                     entry_stream
                         .map(move |result| {
                             match result {
-                                Ok(entry) => {
-                                    let timestamp_info = entry.timestamp_info;
-                                    match String::from_utf8(entry.data) {
-                                        Ok(data) => {
-                                            let message = Message {
-                                                timestamp_millis: timestamp_info.timestamp_millis,
-                                                timestamp_sec: timestamp_info.timestamp_sec,
-                                                timestamp_sub_sec: timestamp_info.timestamp_sub_sec,
-                                                data,
-                                            };
-
-                                            // Filter by timestamp
-                                            let msg_time = DateTime::<Utc>::from_timestamp_millis(message.timestamp_millis);
-                                            if let Some(msg_time) = msg_time {
-                                                if msg_time >= from && msg_time <= to {
-                                                    Some(Ok(message))
-                                                } else {
-                                                    None
-                                                }
-                                            } else {
-                                                None
-                                            }
-                                        }
-                                        Err(err) => {
-                                            tracing::error!("Error decoding message data: {}", err);
-                                            Some(Err(anyhow::anyhow!("Error decoding message data: {}", err)))
-                                        }
-                                    }
+                                // Filter by timestamp, unless sliced by rows
+                                Ok(entry) if by_rows || time_filter.contains_entry(&entry) => {
+                                    metrics::counter!(STREAMED_BYTES).increment(entry.data.len() as u64);
+                                    Some(Ok(entry))
                                 }
+                                Ok(_) => None,
                                 Err(e) => Some(Err(anyhow::anyhow!("Error reading entry: {:?}", e))),
                             }
                         })
                         .filter_map(|item| async move { item })
                         .boxed()
                 }
-                Err(_) => stream::once(async move { Err(anyhow::anyhow!("Failed to stream parquet file")) }).boxed(),
+                Err(err) => stream::once(async move { Err(anyhow::Error::from(err).context("Failed to stream parquet file")) }).boxed(),
             }
-        }
+        };
+        (date, open.boxed())
     }).collect();
 
-    // Convert the vector of futures into a stream and flatten
-    stream::iter(file_streams)
-        .then(|fut| fut)
-        .flatten()
-        .boxed()
+    // Files may overlap in time, e.g. with writes straddling midnight, so merge them into a single timeline
+    let entries = merge_streams(file_streams, order, |entry: &s9_parquet::Entry| {
+        (entry.timestamp_info.timestamp_millis, entry.timestamp_info.timestamp_sub_sec)
+    });
+
+    let entries = if query.dedup {
+        // Duplicates across a file boundary share the millisecond, so a window over it is enough
+        let mut dedup_window = DedupWindow::default();
+        entries
+            .filter(move |result| {
+                let is_new = match result {
+                    Ok(entry) => dedup_window.is_new(entry.timestamp_info.timestamp_millis, entry.timestamp_info.timestamp_sub_sec, &entry.data),
+                    Err(_) => true,
+                };
+                future::ready(is_new)
+            })
+            .boxed()
+    } else {
+        entries.boxed()
+    };
+
+    let entries = match query.speed {
+        Some(speed) if speed > 0.0 => throttle_entries(entries, speed).boxed(),
+        _ => entries,
+    };
+    // The files count as open until the stream is dropped
+    let entries = entries
+        .map(move |entry| {
+            let _open_files = &open_files;
+            entry
+        })
+        .boxed();
+    (file_count, entries)
+}
+
+/// Who reserves the files a stream of `s_market_entries` opens within `max_open_files`.
+#[derive(Clone, Copy, PartialEq)]
+enum OpenFiles {
+    /// The stream itself, all files it opens at once before opening the first one.
+    Reserve,
+    /// The caller, e.g. a replay once for all of its sources.
+    Reserved,
+}
+
+/// The most files `merge_streams` opens at once, those of a day and the day after.
+fn merged_open_files(files: &[FileMetadata]) -> usize {
+    let mut files_per_date = BTreeMap::<NaiveDate, usize>::new();
+    for file in files {
+        *files_per_date.entry(file.date).or_default() += 1;
+    }
+    files_per_date.iter()
+        .map(|(date, count)| {
+            let next_day = date.succ_opt().and_then(|next_day| files_per_date.get(&next_day));
+            count + next_day.unwrap_or(&0)
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// The flush interval of `flush_count` without `flush_ms`, short enough to not be noticeable interactively.
+const DEFAULT_FLUSH_MILLIS: u64 = 100;
+
+/// The longest `flush_ms`, buffering any longer would rather look like a stalled stream.
+const MAX_FLUSH_MILLIS: u64 = 10_000;
+
+/// The slowest replay speed, which keeps the delays of a maximum time range within `Duration`.
+const MIN_REPLAY_SPEED: f64 = 0.001;
+
+/// Delays each entry until its time relative to the first entry has passed, divided by `speed`,
+/// replaying the entries at the pace they originally occurred at with `speed` 1.
+///
+/// The entries are scheduled from the start of the replay, so slow consumers don't accumulate drift.
+/// A disconnected client drops the stream, which cancels a pending sleep.
+fn throttle_entries(
+    entries: BoxStream<'static, anyhow::Result<s9_parquet::Entry>>,
+    speed: f64,
+) -> impl Stream<Item = anyhow::Result<s9_parquet::Entry>> {
+    let mut start: Option<(i64, tokio::time::Instant)> = None;
+    entries.then(move |result| {
+        let deadline = result.as_ref().ok().map(|entry| {
+            let timestamp_millis = entry.timestamp_info.timestamp_millis;
+            let (first_millis, started) = *start.get_or_insert((timestamp_millis, tokio::time::Instant::now()));
+            // Descending streams go back in time, so only the distance counts
+            let elapsed_millis = (timestamp_millis - first_millis).unsigned_abs() as f64 / speed;
+            started + Duration::from_secs_f64(elapsed_millis / 1000.0)
+        });
+        async move {
+            if let Some(deadline) = deadline {
+                tokio::time::sleep_until(deadline).await;
+            }
+            result
+        }
+    })
+}
+
+/// A stream to merge by `merge_streams`, e.g. of a file, of its date and the future opening it.
+type PendingStream<T> = (NaiveDate, BoxFuture<'static, BoxStream<'static, anyhow::Result<T>>>);
+
+/// Merges the time ordered streams, e.g. of several files sorted by date in the `order`, into one stream
+/// in global time order by the `sort_key` of the items.
+///
+/// Only the next item of each stream is buffered, the earliest one is emitted, or the latest one with
+/// `Order::Desc`. Items with equal keys keep the order of the streams. Errors are passed on as read.
+///
+/// As messages only spill over into the adjacent day, a file is opened once the open files reach its previous day,
+/// or its next day with `Order::Desc`. So no matter the time range, only the files of about two days are read ahead
+/// of a slow client, and reading pauses while the client doesn't consume.
+fn merge_streams<T, K>(files: Vec<PendingStream<T>>, order: Order, sort_key: fn(&T) -> K) -> impl Stream<Item = anyhow::Result<T>>
+where
+    T: Send + 'static,
+    K: Ord,
+{
+    let pending: VecDeque<PendingStream<T>> = files.into();
+    let heads: Vec<(NaiveDate, BoxStream<'static, anyhow::Result<T>>, Option<T>)> = Vec::new();
+
+    stream::unfold((pending, heads), move |(mut pending, mut heads)| async move {
+        loop {
+            // Open the files that may hold entries interleaved with the open ones
+            while let Some((date, _)) = pending.front() {
+                let date = *date;
+                let adjacent = match order {
+                    Order::Asc => heads.iter().map(|(date, _, _)| *date).min()
+                        .map_or(true, |earliest| earliest.succ_opt().map_or(true, |next_day| date <= next_day)),
+                    Order::Desc => heads.iter().map(|(date, _, _)| *date).max()
+                        .map_or(true, |latest| latest.pred_opt().map_or(true, |previous_day| date >= previous_day)),
+                };
+                if !adjacent {
+                    break;
+                }
+                if let Some((date, open)) = pending.pop_front() {
+                    heads.push((date, open.await, None));
+                }
+            }
+
+            // Refill the heads taken before, dropping exhausted streams
+            let mut exhausted = false;
+            let mut index = 0;
+            while index < heads.len() {
+                let (_, stream, head) = &mut heads[index];
+                if head.is_none() {
+                    match stream.next().await {
+                        Some(Ok(entry)) => *head = Some(entry),
+                        Some(Err(err)) => return Some((Err(err), (pending, heads))),
+                        None => {
+                            heads.remove(index);
+                            exhausted = true;
+                            continue;
+                        }
+                    }
+                }
+                index += 1;
+            }
+
+            // A dropped stream may let further files open
+            if !exhausted || pending.is_empty() {
+                break;
+            }
+        }
+
+        let (next, _) = heads
+            .iter()
+            .enumerate()
+            .filter_map(|(index, (_, _, head))| head.as_ref().map(|item| (index, sort_key(item))))
+            .min_by(|(_, a), (_, b)| match order {
+                Order::Asc => a.cmp(b),
+                Order::Desc => b.cmp(a),
+            })?;
+        let item = heads[next].2.take()?;
+        Some((Ok(item), (pending, heads)))
+    })
+}
+
+/// Decodes an entry with `codec::entry_to_message`, logging and counting the entries that fail to decode.
+fn decode_entry(entry: s9_parquet::Entry, symbol: &str, base64_fallback: bool) -> anyhow::Result<Message> {
+    codec::entry_to_message(entry, symbol, base64_fallback).map_err(|err| {
+        tracing::error!("Error decoding message data: {}", err);
+        metrics::counter!(DECODE_ERRORS).increment(1);
+        err.into()
+    })
 }
 
 
+#[utoipa::path(
+    get,
+    path = "/api/v1/market-data/{exchange}/{market_type}/{stream}/{symbol}",
+    tag = "market-data",
+    params(
+        ("exchange" = String, Path, description = "The exchange, e.g. `binance`"),
+        ("market_type" = String, Path, description = "The market type, e.g. `spot`"),
+        ("stream" = String, Path, description = "The stream, e.g. `trade`, or a comma-separated list like `trade,bookTicker` merged by timestamp"),
+        ("symbol" = String, Path, description = "The symbol, e.g. `ethusdt`, a glob like `*usdt`, or a comma-separated list of these"),
+        QueryParams,
+    ),
+    responses(
+        (status = 200, description = "A page of the messages of the time range, or CSV by `Accept`", body = ApiResponse<Vec<Message>>),
+        (status = 304, description = "The `If-None-Match` ETag still matches the files of the time range"),
+        (status = 400, description = "Invalid path or query parameters"),
+        (status = 404, description = "The symbol has no market data"),
+        (status = 413, description = "The time range holds more than `max_response_messages` messages"),
+        (status = 416, description = "The time range lies entirely before or after the files of the symbol"),
+        (status = 422, description = "Files are missing for some dates of the time range with `strict`"),
+    )
+)]
 async fn get_market_data(
     ctx: Extension<ApiContext>,
     Path((exchange, market_type, stream, symbol)): Path<(String, String, String, String)>,
-    Query(query): Query<QueryParams>,
-) -> anyhow::Result<Json<ApiResponse<Vec<Message>>>, StatusCode>
+    Query(mut query): Query<QueryParams>,
+    RawQuery(raw_query): RawQuery,
+    headers: HeaderMap,
+) -> Result<Response, Error>
 {
+    metrics::counter!(HTTP_REQUESTS, "route" => "/api/v1/market-data").increment(1);
+    let symbol = ctx.symbol_aliases.resolve_list(&symbol);
     tracing::info!("loading batch market data for {}/{}/{}/{}", exchange, market_type, stream, symbol);
 
-    let file_paths = if let (Some(from), Some(to)) = (query.from, query.to) {
+    query.resolve_time_range().map_err(Error::BadRequest)?;
+
+    // The stream path segment may carry a comma-separated list of streams, e.g. `trade,bookTicker`,
+    // whose messages are merged into a single timeline
+    let streams: Vec<&str> = stream.split(',').collect();
+    for stream in &streams {
+        validate_stream_path(&ctx.config, &exchange, &market_type, stream).map_err(Error::BadRequest)?;
+    }
+    validate_path_segment(&symbol).map_err(Error::BadRequest)?;
+
+    if let Some(limit) = query.limit {
+        if limit == 0 || limit > ctx.config.max_page_limit {
+            return Err(Error::BadRequest(format!("limit must be between 1 and {}", ctx.config.max_page_limit)));
+        }
+    }
+
+    // The symbol path segment may carry a comma-separated list of symbols or globs, e.g. `ethusdt,*btc`
+    let mut stream_symbols = Vec::new();
+    for stream in &streams {
+        stream_symbols.push((*stream, expand_symbols(&ctx, &exchange, &market_type, stream, &symbol).await?));
+    }
+
+    if let Some(last) = query.last {
+        if last == 0 || last > ctx.config.max_page_limit {
+            return Err(Error::BadRequest(format!("last must be between 1 and {}", ctx.config.max_page_limit)));
+        }
+        let [(stream, symbols)] = stream_symbols.as_slice() else {
+            return Err(Error::BadRequest("last supports a single stream only".to_string()));
+        };
+        return last_messages(&ctx, &exchange, &market_type, stream, symbols, last, &query).await;
+    }
+
+    let (from, to) = match (query.from, query.to, ctx.config.default_window_minutes) {
+        (Some(from), Some(to), _) => (from, to),
+        (None, None, Some(window_minutes)) => {
+            let mut latest_end = None;
+            for (stream, symbols) in &stream_symbols {
+                let stream_end = latest_data_end(&ctx, &exchange, &market_type, stream, symbols, query.refresh).await
+                    .map_err(Error::Anyhow)?;
+                latest_end = latest_end.max(stream_end);
+            }
+            match latest_end {
+                Some(to) => (to - TimeDelta::minutes(window_minutes.into()), to),
+                None => return Err(Error::DataNotFound(format!("No files found for symbol: {}", symbol))),
+            }
+        }
+        _ => return Err(Error::BadRequest("Missing from/to parameters".to_string())),
+    };
+    validate_time_range(&from, &to, ctx.config.max_query_range_days).map_err(Error::BadRequest)?;
+    let fields = query.field_selection().map_err(Error::BadRequest)?;
+    let batch_size = query.record_batch_size(&ctx.config).map_err(Error::BadRequest)?;
+    let rows = query.row_range().map_err(Error::BadRequest)?;
+
+    let tick_parser = if query.parse {
+        // The messages of merged streams would need a parser each
+        let [stream] = streams.as_slice() else {
+            return Err(Error::BadRequest("parse supports a single stream only".to_string()));
+        };
+        let tick_parser = tick::tick_parser(&exchange, stream)
+            .ok_or_else(|| Error::BadRequest(format!("parse is not supported for {}/{}", exchange, stream)))?;
+        Some(tick_parser)
+    } else {
+        None
+    };
+
+    let interval_millis = match query.interval.as_deref() {
+        Some(interval) => match candle::parse_interval_millis(interval) {
+            Some(interval_millis) => Some(interval_millis),
+            None => return Err(Error::BadRequest("interval must be like 1s, 1m, 1h or 1d".to_string())),
+        },
+        None => None,
+    };
+
+    let mut files_to_read = Vec::new();
+    let mut missing_dates = Vec::new();
+    // The first and last date of the files of all symbols, to tell a time range outside of them from an empty one
+    let mut available_dates: Option<(NaiveDate, NaiveDate)> = None;
+
+    for (stream, symbol) in stream_symbols.iter().flat_map(|(stream, symbols)| symbols.iter().map(move |symbol| (*stream, symbol.as_str()))) {
         // Multi-file query for date range
         let file_finder = FileFinder {
             parquet_file_extension: &ctx.config.parquet_file_extension,
+            date_format: &ctx.config.filename_date_format,
+            separator: &ctx.config.filename_separator,
+            layout: ctx.config.directory_layout,
             base_path: &ctx.config.market_data_path,
+            archive_paths: &ctx.config.archive_data_paths,
+            remote_store: ctx.remote_store.as_deref(),
             exchange: &exchange,
             market_type: &market_type,
-            stream: &stream,
-            symbol: &symbol,
+            stream,
+            symbol,
             time_slice: &TimeSlice {
                 from: &from,
                 to: &to,
+                inclusive_end: query.inclusive_end,
             },
+            scan_cache: &ctx.scan_cache,
+            include_boundary_files: ctx.config.include_boundary_files,
+            scan_depth: ctx.config.scan_depth,
+            refresh: query.refresh,
         };
 
-        file_finder.find_files().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-    } else {
-        return Err(StatusCode::BAD_REQUEST);
+        let files = match find_files(&file_finder).await {
+            Ok(files) => files,
+            // Skip a missing symbol of a basket rather than failing the whole request
+            Err(err) if stream_symbols.len() > 1 || stream_symbols.iter().any(|(_, symbols)| symbols.len() > 1) => {
+                tracing::warn!("Skipping symbol {}: {}", symbol, err);
+                continue;
+            }
+            Err(err) if err.is::<SymbolNotFound>() => return Err(Error::DataNotFound(err.to_string())),
+            Err(err) => return Err(Error::Anyhow(err.context("Failed to find files"))),
+        };
+
+        if files.is_empty() {
+            if let Ok(all_files) = file_finder.all_file_metas().await {
+                if let (Some(first), Some(last)) = (all_files.first(), all_files.last()) {
+                    available_dates = Some(match available_dates {
+                        Some((first_date, last_date)) => (first_date.min(first.date), last_date.max(last.date)),
+                        None => (first.date, last.date),
+                    });
+                }
+            }
+        }
+
+        missing_dates.extend(file_finder.missing_dates(&files));
+        // Rows are sliced from the day's file only, not from boundary files
+        files_to_read.extend(files
+            .into_iter()
+            .filter(|file_meta| rows.is_none() || file_meta.date == from.date_naive())
+            .map(|file_meta| (file_meta, symbol, stream)));
+    }
+
+    if files_to_read.is_empty() {
+        if let Some((first_date, last_date)) = available_dates {
+            if to.date_naive() < first_date || from.date_naive() > last_date {
+                return Err(Error::RangeNotSatisfiable(format!(
+                    "no market data between {} and {}, available are the dates {} to {}",
+                    from.to_rfc3339_opts(SecondsFormat::AutoSi, true), to.to_rfc3339_opts(SecondsFormat::AutoSi, true), first_date, last_date)));
+            }
+        }
+    }
+
+    // A date is missing if any of the symbols lacks its file
+    missing_dates.sort();
+    missing_dates.dedup();
+
+    if query.dry_run {
+        let files = files_to_read
+            .into_iter()
+            .map(|(file_meta, symbol, stream)| DryRunFile {
+                path: file_meta.path,
+                stream: stream.to_string(),
+                symbol: symbol.to_string(),
+                date: file_meta.date,
+            })
+            .collect();
+        return Ok(Json(DryRunResponse { files, missing_dates }).into_response());
+    }
+
+    if query.strict && !missing_dates.is_empty() {
+        let missing_dates = missing_dates.iter().map(|date| date.to_string()).collect::<Vec<_>>();
+        let message = format!("missing market data files for dates: {}", missing_dates.join(", "));
+        return Err(Error::MissingFiles(message));
+    }
+
+    // Historical files don't change, so a client polling the same time range can skip reading them again
+    let validators = Validators {
+        etag: market_data_etag(&files_to_read, raw_query.as_deref(), &headers).await,
+        last_modified: last_modified(&files_to_read).await,
     };
+    if validators.not_modified(&headers) {
+        let mut response = StatusCode::NOT_MODIFIED.into_response();
+        validators.insert_into(response.headers_mut());
+        response.headers_mut().insert(CACHE_CONTROL, cache_control(&to, &ctx.config));
+        return Ok(response);
+    }
+
+    let time_filter = TimeFilter::new(from, to, query.inclusive_end);
+    let base64_fallback = query.base64_fallback(&ctx.config);
+    let file_count = files_to_read.len();
+
+    // Read the files concurrently, but never open more than the configured number at once
+    let ctx_ref = &ctx;
+    let time_filter_ref = &time_filter;
+    let columns = query.extra_columns();
+    let columns_ref = &columns;
+    let rows_ref = rows.as_ref();
+    let multiple_streams = streams.len() > 1;
+    let mut file_reads = stream::iter(files_to_read)
+        .map(|(file_meta, symbol, stream)| async move {
+            if rows_ref.is_none() && !file_may_contain(&ctx_ref.config, &file_meta.path, time_filter_ref).await {
+                return Ok(Vec::new());
+            }
+            let mut messages = read_parquet_file(ctx_ref, &file_meta.path, symbol, base64_fallback, batch_size, columns_ref, rows_ref).await?;
+            // Merged streams are only told apart by the stream of each message
+            if multiple_streams {
+                for message in &mut messages {
+                    message.stream = Some(stream.to_string());
+                }
+            }
+            Ok::<_, Error>(messages)
+        })
+        .buffer_unordered(ctx.config.file_read_concurrency.max(1));
 
+    let max_messages = ctx.config.max_response_messages;
     let mut all_messages = Vec::new();
+    let mut failed_files = 0;
+    let mut truncated = false;
+    while let Some(messages) = file_reads.next().await {
+        match messages {
+            // Filter by exact timestamps, unless sliced by rows
+            Ok(messages) if rows.is_some() => all_messages.extend(messages),
+            Ok(messages) => all_messages.extend(messages.into_iter().filter(|msg| time_filter.contains_message(msg))),
+            Err(status) if query.strict => return Err(status),
+            // The failure is logged by `read_parquet_file`, return the messages of the other files
+            Err(_) => failed_files += 1,
+        }
 
-    if ! file_paths.is_empty() {
-        for file_path in file_paths {
-            let messages = read_parquet_file(&ctx, &file_path).await?;
-            all_messages.extend(messages);
+        if max_messages > 0 && all_messages.len() > max_messages {
+            match ctx.config.response_limit_mode {
+                ResponseLimitMode::Reject => return Err(Error::PayloadTooLarge(format!(
+                    "the query exceeds the maximum of {} messages, use the streaming endpoint /stream/v1/market-data for large time ranges",
+                    max_messages
+                ))),
+                // Files complete in any order, so only the earliest messages read so far are kept
+                ResponseLimitMode::Truncate => {
+                    all_messages.sort_by_key(|msg| msg.timestamp_millis);
+                    all_messages.truncate(max_messages);
+                    truncated = true;
+                }
+            }
         }
+    }
+
+    // Files complete in any order and symbols interleave, so merge all messages into a single timeline
+    all_messages.sort_by_key(|msg| msg.timestamp_millis);
 
-        // Filter by exact timestamps
-        if let (Some(from), Some(to)) = (query.from, query.to) {
-            all_messages.retain(|msg| {
-                let msg_time = DateTime::<Utc>::from_timestamp_millis(msg.timestamp_millis);
-                if let Some(msg_time) = msg_time {
-                    msg_time >= from && msg_time <= to
-                } else {
-                    false
+    if query.dedup {
+        let mut dedup_window = DedupWindow::default();
+        all_messages.retain(|msg| dedup_window.is_new(msg.timestamp_millis, msg.timestamp_sub_sec, msg.data.as_bytes()));
+    }
+
+    // Candles are aggregated over the whole time range, so they are neither paginated nor encoded as CSV
+    if let Some(interval_millis) = interval_millis {
+        let fields = CandleFields {
+            price: &ctx.config.candle_price_field,
+            quantity: &ctx.config.candle_quantity_field,
+        };
+        let candles = candle::build_candles(&all_messages, interval_millis, &fields, query.skip_empty);
+        let mut response = Json(CandleResponse { candles }).into_response();
+        mark_partial(&mut response, failed_files, truncated, &validators, cache_control(&to, &ctx.config));
+        return Ok(response);
+    }
+
+    let next_cursor = paginate(&mut all_messages, query.cursor, query.offset, query.limit);
+
+    if query.include_meta {
+        all_messages = all_messages
+            .into_iter()
+            .map(|mut msg| {
+                let msg_stream = msg.stream.take().unwrap_or_else(|| stream.clone());
+                msg.with_meta(&exchange, &market_type, &msg_stream)
+            })
+            .collect();
+    }
+
+    // Lets clients tell empty results from errors without parsing the body, the length is set by axum
+    let message_count = HeaderValue::from(all_messages.len());
+
+    let mut response = match (ResponseFormat::from_headers(&headers), tick_parser) {
+        (ResponseFormat::Json, Some(tick_parser)) => {
+            let mut messages = Vec::with_capacity(all_messages.len());
+            for message in all_messages {
+                match tick_parser(&message.data) {
+                    Some(tick) => messages.push(ParsedOrRawMessage::Parsed(ParsedMessage::new(&message, tick))),
+                    None if query.parse_strict => {
+                        return Err(Error::BadRequest(format!("unparseable payload at {}", message.timestamp_millis)));
+                    }
+                    None => messages.push(ParsedOrRawMessage::Raw(ProjectedMessage::new(message, fields, &ctx.config).with_raw_json(query.raw_json))),
                 }
-            });
+            }
+            Json(ApiResponse{ messages, file_count, next_cursor, missing_dates }).into_response()
         }
+        (ResponseFormat::Json, None) => {
+            let messages: Vec<ProjectedMessage> = all_messages
+                .into_iter()
+                .map(|message| ProjectedMessage::new(message, fields, &ctx.config).with_raw_json(query.raw_json))
+                .collect();
+            Json(ApiResponse{ messages, file_count, next_cursor, missing_dates }).into_response()
+        }
+        (ResponseFormat::Csv, _) => {
+            let body = format::csv_body(&all_messages).context("Error encoding messages as csv")?;
+
+            let mut response = ([(CONTENT_TYPE, "text/csv")], body).into_response();
+            // CSV has no place for the cursor in the body, so it is passed as header instead
+            if let Some(next_cursor) = next_cursor {
+                response.headers_mut().insert("x-next-cursor", HeaderValue::from(next_cursor));
+            }
+            if !missing_dates.is_empty() {
+                let missing_dates = missing_dates.iter().map(|date| date.to_string()).collect::<Vec<_>>();
+                if let Ok(value) = HeaderValue::from_str(&missing_dates.join(",")) {
+                    response.headers_mut().insert("x-missing-dates", value);
+                }
+            }
+            response
+        }
+        // Arrow entries and events are only streamed, see `stream_market_data`
+        (ResponseFormat::Arrow | ResponseFormat::EventStream, _) => return Err(Error::NotAcceptable),
+    };
+    response.headers_mut().insert("x-message-count", message_count);
+    response.headers_mut().insert("x-file-count", HeaderValue::from(file_count));
+    mark_partial(&mut response, failed_files, truncated, &validators, cache_control(&to, &ctx.config));
+    Ok(response)
+}
+
+/// Rejects path parameters that could escape the market data directory or aren't on the allow-lists.
+fn validate_stream_path(config: &Config, exchange: &str, market_type: &str, stream: &str) -> Result<(), String> {
+    for segment in [exchange, market_type, stream] {
+        validate_path_segment(segment)?;
     }
+    if !config.allowed_exchanges.is_empty() && !config.allowed_exchanges.iter().any(|allowed| allowed == exchange) {
+        return Err(format!("exchange {} is not allowed", exchange));
+    }
+    if !config.allowed_streams.is_empty() && !config.allowed_streams.iter().any(|allowed| allowed == stream) {
+        return Err(format!("stream {} is not allowed", stream));
+    }
+    Ok(())
+}
 
-    Ok(Json(ApiResponse{ messages: all_messages }))
+/// Rejects a path parameter that isn't a plain directory or file name, e.g. `..` or `binance/../..`.
+fn validate_path_segment(segment: &str) -> Result<(), String> {
+    if segment.is_empty() || segment.contains("..") || segment.contains(['/', '\\', '\0']) {
+        return Err(format!("invalid path segment {:?}", segment));
+    }
+    Ok(())
 }
 
-async fn read_parquet_file(ctx: &Extension<ApiContext>, file_path: &PathBuf) -> anyhow::Result<Vec<Message>, StatusCode> {
-    let batch_size = &ctx.config.parquet_reader_record_batch_size;
-    let reader = s9_parquet::AsyncParquetReader::new(file_path, *batch_size).await
-        .map_err(|err| {
-            tracing::error!("Error reading parquet file: {}", err);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+/// Checks that the queried time range is ordered and doesn't exceed the maximum span of days.
+fn validate_time_range(from: &DateTime<Utc>, to: &DateTime<Utc>, max_range_days: u32) -> Result<(), String> {
+    if from > to {
+        return Err("from must be <= to".to_string());
+    }
+    if *to - *from > TimeDelta::days(max_range_days.into()) {
+        return Err(format!("time range from/to must not exceed {} days", max_range_days));
+    }
+    Ok(())
+}
 
-    let entries = reader.read().await
-        .map_err(|err| {
-            tracing::error!("Error reading entries from parquet file: {}", err);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+/// The exact time range messages are filtered by, once the files have been selected by date.
+///
+/// `from` is inclusive and `to` exclusive, so adjacent time ranges don't overlap, unless
+/// `inclusive_end` is set.
+///
+/// Compares at millisecond precision by default. If the bounds carry sub-millisecond digits, the
+/// full timestamp is rebuilt from `timestamp_sec` and the nanoseconds in `timestamp_sub_sec` instead,
+/// to slice between messages that share the same millisecond.
+#[derive(Clone, Copy, Debug)]
+struct TimeFilter {
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    inclusive_end: bool,
+    precise: bool,
+}
 
-    let mut messages: Vec<Message> = Vec::with_capacity(entries.len());
-    for entry in entries {
-        let timestamp_info = entry.timestamp_info;
-        let data = String::from_utf8(entry.data);
-        let data = match data {
-            Ok(data) => data,
-            Err(err) => {
-                tracing::error!("Error decoding message data: {}", err);
-                continue;
-            }
+impl TimeFilter {
+
+    fn new(from: DateTime<Utc>, to: DateTime<Utc>, inclusive_end: bool) -> Self {
+        TimeFilter {
+            from,
+            to,
+            inclusive_end,
+            precise: has_sub_millis(&from) || has_sub_millis(&to),
+        }
+    }
+
+    fn contains_message(&self, message: &Message) -> bool {
+        self.contains(message.timestamp_millis, message.timestamp_sec, message.timestamp_sub_sec)
+    }
+
+    fn contains_entry(&self, entry: &s9_parquet::Entry) -> bool {
+        let timestamp_info = &entry.timestamp_info;
+        self.contains(timestamp_info.timestamp_millis, timestamp_info.timestamp_sec, timestamp_info.timestamp_sub_sec)
+    }
+
+    /// Checks whether the millisecond range `min..=max` may hold timestamps of the filter.
+    fn overlaps_millis(&self, min: i64, max: i64) -> bool {
+        max >= self.from.timestamp_millis() && min <= self.to.timestamp_millis()
+    }
+
+    /// Checks whether the timestamp lies within `from..to`, or `from..=to` with `inclusive_end`.
+    fn contains(&self, timestamp_millis: i64, timestamp_sec: i64, timestamp_sub_sec: i32) -> bool {
+        let msg_time = if self.precise {
+            u32::try_from(timestamp_sub_sec)
+                .ok()
+                .and_then(|nanos| DateTime::<Utc>::from_timestamp(timestamp_sec, nanos))
+        } else {
+            DateTime::<Utc>::from_timestamp_millis(timestamp_millis)
         };
-        let message = Message {
-            timestamp_millis: timestamp_info.timestamp_millis,
-            timestamp_sec: timestamp_info.timestamp_sec,
-            timestamp_sub_sec: timestamp_info.timestamp_sub_sec,
-            data,
+
+        match msg_time {
+            Some(msg_time) => self.contains_time(&msg_time),
+            None => false,
+        }
+    }
+
+    fn contains_time(&self, time: &DateTime<Utc>) -> bool {
+        *time >= self.from && (*time < self.to || (self.inclusive_end && *time == self.to))
+    }
+
+    /// Checks whether all timestamps of the millisecond range `min..=max` are certainly within the filter.
+    fn covers_millis(&self, min: i64, max: i64) -> bool {
+        // Compared by nanoseconds, a message may lie anywhere within the last millisecond
+        let slack = if self.precise { TimeDelta::nanoseconds(999_999) } else { TimeDelta::zero() };
+        match (DateTime::<Utc>::from_timestamp_millis(min), DateTime::<Utc>::from_timestamp_millis(max)) {
+            (Some(first), Some(last)) => self.contains_time(&first) && self.contains_time(&(last + slack)),
+            _ => false,
+        }
+    }
+}
+
+/// Whether the timestamp statistics in the footer of a parquet file allow it to hold messages of the time filter.
+///
+/// Only whole files are skipped, as the reader has no row group selection. Files without usable
+/// statistics are always read.
+async fn file_may_contain(config: &Config, file_path: &PathBuf, time_filter: &TimeFilter) -> bool {
+    if !config.prune_by_statistics {
+        return true;
+    }
+    let may_contain = match parquet_meta::row_group_timestamps(file_path, &config.parquet_timestamp_column).await {
+        Ok(Some(row_groups)) => match parquet_meta::timestamp_range(&row_groups) {
+            Some((min, max)) => time_filter.overlaps_millis(min, max),
+            None => false,
+        },
+        Ok(None) => true,
+        Err(err) => {
+            tracing::debug!("Unable to read timestamp statistics of {:?}: {}", file_path, err);
+            true
+        }
+    };
+    if !may_contain {
+        metrics::counter!(PARQUET_FILES_PRUNED).increment(1);
+    }
+    may_contain
+}
+
+/// Detects duplicate messages, keyed by `(timestamp_millis, timestamp_sub_sec, data)`.
+///
+/// Expects the messages in time order and only remembers the keys of the current millisecond, as
+/// duplicates, e.g. a trade near midnight written to the files of both days, share their timestamp.
+#[derive(Debug, Default)]
+struct DedupWindow {
+    timestamp_millis: Option<i64>,
+    seen: HashSet<(i32, Vec<u8>)>,
+}
+
+impl DedupWindow {
+
+    fn is_new(&mut self, timestamp_millis: i64, timestamp_sub_sec: i32, data: &[u8]) -> bool {
+        if self.timestamp_millis != Some(timestamp_millis) {
+            self.timestamp_millis = Some(timestamp_millis);
+            self.seen.clear();
+        }
+        self.seen.insert((timestamp_sub_sec, data.to_vec()))
+    }
+}
+
+/// Whether the time carries digits below millisecond precision.
+fn has_sub_millis(time: &DateTime<Utc>) -> bool {
+    time.timestamp_subsec_nanos() % 1_000_000 != 0
+}
+
+/// Signals with `x-partial` and `x-failed-files` headers that files failed to read and were skipped,
+/// and with `x-truncated` that messages beyond `max_response_messages` were dropped.
+/// Otherwise sets the ETag and `Cache-Control`, as only complete responses may be cached.
+fn mark_partial(response: &mut Response, failed_files: usize, truncated: bool, validators: &Validators, cache_control: HeaderValue) {
+    if truncated {
+        response.headers_mut().insert("x-truncated", HeaderValue::from_static("true"));
+    }
+    if failed_files > 0 {
+        response.headers_mut().insert("x-partial", HeaderValue::from_static("true"));
+        response.headers_mut().insert("x-failed-files", HeaderValue::from(failed_files));
+    }
+    if failed_files > 0 || truncated {
+        response.headers_mut().insert(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+        return;
+    }
+    validators.insert_into(response.headers_mut());
+    response.headers_mut().insert(CACHE_CONTROL, cache_control);
+}
+
+/// The validators of a batch response, which let polling clients skip unchanged responses.
+struct Validators {
+    etag: Option<HeaderValue>,
+    /// The latest modification time of the files read.
+    last_modified: Option<DateTime<Utc>>,
+}
+
+impl Validators {
+
+    /// Whether the client's copy is current, by `If-None-Match` or otherwise by `If-Modified-Since`,
+    /// which is ignored alongside `If-None-Match` as in RFC 9110.
+    fn not_modified(&self, headers: &HeaderMap) -> bool {
+        if headers.contains_key(IF_NONE_MATCH) {
+            return self.etag.as_ref().is_some_and(|etag| etag_matches(headers, etag));
+        }
+        let if_modified_since = headers
+            .get(IF_MODIFIED_SINCE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| DateTime::parse_from_rfc2822(value).ok());
+        match (self.last_modified, if_modified_since) {
+            // HTTP dates have no fractional seconds
+            (Some(last_modified), Some(if_modified_since)) => last_modified.timestamp() <= if_modified_since.timestamp(),
+            _ => false,
+        }
+    }
+
+    fn insert_into(&self, headers: &mut HeaderMap) {
+        if let Some(etag) = &self.etag {
+            headers.insert(ETAG, etag.clone());
+        }
+        let last_modified = self.last_modified
+            .and_then(|last_modified| HeaderValue::from_str(&last_modified.format("%a, %d %b %Y %H:%M:%S GMT").to_string()).ok());
+        if let Some(last_modified) = last_modified {
+            headers.insert(LAST_MODIFIED, last_modified);
+        }
+    }
+}
+
+/// The latest modification time of the files to read, by their metadata only.
+///
+/// Returns `None` without files or if the metadata of a file is not available, e.g. of a remote file not cached yet.
+async fn last_modified(files: &[(FileMetadata, &str, &str)]) -> Option<DateTime<Utc>> {
+    let mut last_modified = None;
+    for (file_meta, _, _) in files {
+        let modified = tokio::fs::metadata(&file_meta.path).await.ok()?.modified().ok()?;
+        last_modified = last_modified.max(Some(DateTime::<Utc>::from(modified)));
+    }
+    last_modified
+}
+
+/// The `Cache-Control` of a query ending at `to`, shared caches may keep responses of past days,
+/// as their files are complete. Queries touching the current day must be revalidated.
+fn cache_control(to: &DateTime<Utc>, config: &Config) -> HeaderValue {
+    let today = Utc::now().date_naive().and_time(NaiveTime::MIN).and_utc();
+    if config.historical_max_age_secs == 0 || *to > today {
+        return HeaderValue::from_static("no-cache");
+    }
+    HeaderValue::from_str(&format!("public, max-age={}", config.historical_max_age_secs))
+        .unwrap_or(HeaderValue::from_static("no-cache"))
+}
+
+/// Computes a weak ETag from the paths, modification times and sizes of the files to read,
+/// together with the query and the `Accept` header, which determine the response built from them.
+///
+/// Returns `None` if the metadata of a file is not available, e.g. of a remote file not cached yet.
+async fn market_data_etag(files: &[(FileMetadata, &str, &str)], raw_query: Option<&str>, headers: &HeaderMap) -> Option<HeaderValue> {
+    let mut hasher = Sha256::new();
+    for (file_meta, _, _) in files {
+        let metadata = tokio::fs::metadata(&file_meta.path).await.ok()?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        hasher.update(file_meta.path.to_string_lossy().as_bytes());
+        hasher.update(modified.as_nanos().to_le_bytes());
+        hasher.update(metadata.len().to_le_bytes());
+    }
+    hasher.update(raw_query.unwrap_or_default().as_bytes());
+    if let Some(accept) = headers.get(ACCEPT) {
+        hasher.update(accept.as_bytes());
+    }
+
+    let digest = hasher.finalize();
+    HeaderValue::from_str(&format!("W/\"{:x}\"", digest)).ok()
+}
+
+/// Whether `If-None-Match` lists the ETag, compared weakly, or is `*`.
+fn etag_matches(headers: &HeaderMap, etag: &HeaderValue) -> bool {
+    let Some(if_none_match) = headers.get(IF_NONE_MATCH).and_then(|value| value.to_str().ok()) else {
+        return false;
+    };
+    let etag = etag.to_str().unwrap_or_default().trim_start_matches("W/");
+    if_none_match
+        .split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+/// Splits the comma-separated symbols and expands globs like `*usdt` to the symbols of the stream directory.
+///
+/// Fails if nothing is left or more symbols match than `max_glob_symbols`, to avoid accidental full scans.
+async fn expand_symbols(
+    ctx: &ApiContext,
+    exchange: &str,
+    market_type: &str,
+    stream: &str,
+    symbol: &str,
+) -> Result<Vec<String>, Error> {
+    let mut symbols = Vec::new();
+    for symbol in symbol.split(',').filter(|symbol| !symbol.is_empty()) {
+        if !is_glob(symbol) {
+            symbols.push(symbol.to_string());
+            continue;
+        }
+
+        let symbol_scanner = SymbolScanner {
+            parquet_file_extension: &ctx.config.parquet_file_extension,
+            date_format: &ctx.config.filename_date_format,
+            separator: &ctx.config.filename_separator,
+            layout: ctx.config.directory_layout,
+            base_path: &ctx.config.market_data_path,
+            archive_paths: &ctx.config.archive_data_paths,
+            remote_store: ctx.remote_store.as_deref(),
+            exchange,
+            market_type,
+            stream,
         };
-        messages.push(message);
+        match symbol_scanner.symbols_matching(symbol).await {
+            Ok(matching) => symbols.extend(matching),
+            Err(err) if err.is::<StreamNotFound>() => return Err(Error::DataNotFound(err.to_string())),
+            Err(err) => return Err(Error::Anyhow(err.context(format!("Error scanning stream {}", stream)))),
+        }
+    }
+    symbols.sort();
+    symbols.dedup();
+
+    if symbols.is_empty() {
+        return Err(Error::DataNotFound(format!("No symbols match {}", symbol)));
+    }
+    if symbols.len() > ctx.config.max_glob_symbols {
+        let message = format!("{} matches {} symbols, more than the maximum of {}", symbol, symbols.len(), ctx.config.max_glob_symbols);
+        return Err(Error::BadRequest(message));
     }
+    Ok(symbols)
+}
+
+/// Returns the end of the newest file's date across the symbols, i.e. midnight of the following day,
+/// or `None` if none of the symbols has a file.
+async fn latest_data_end(
+    ctx: &ApiContext,
+    exchange: &str,
+    market_type: &str,
+    stream: &str,
+    symbols: &[String],
+    refresh: bool,
+) -> anyhow::Result<Option<DateTime<Utc>>> {
+    let mut latest_date: Option<NaiveDate> = None;
+    for symbol in symbols {
+        match latest_file(ctx, exchange, market_type, stream, symbol, refresh).await {
+            Ok(Some(file_meta)) => latest_date = latest_date.max(Some(file_meta.date)),
+            Ok(None) => {}
+            Err(err) if err.is::<SymbolNotFound>() => {}
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(latest_date
+        .and_then(|date| date.succ_opt())
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|time| time.and_utc()))
+}
+
+/// Returns the newest file of the symbol, `None` if the directory has no file of it.
+async fn latest_file(
+    ctx: &ApiContext,
+    exchange: &str,
+    market_type: &str,
+    stream: &str,
+    symbol: &str,
+    refresh: bool,
+) -> anyhow::Result<Option<FileMetadata>> {
+    let files = symbol_files(ctx, exchange, market_type, stream, symbol, refresh).await?;
+    Ok(files.last().cloned())
+}
+
+/// Returns all files of the symbol sorted by date, regardless of any time range.
+async fn symbol_files(
+    ctx: &ApiContext,
+    exchange: &str,
+    market_type: &str,
+    stream: &str,
+    symbol: &str,
+    refresh: bool,
+) -> anyhow::Result<Arc<Vec<FileMetadata>>> {
+    // The time slice is ignored by `all_file_metas`
+    let unbounded = TimeSlice {
+        from: &DateTime::<Utc>::MIN_UTC,
+        to: &DateTime::<Utc>::MAX_UTC,
+        inclusive_end: true,
+    };
+    let file_finder = FileFinder {
+        parquet_file_extension: &ctx.config.parquet_file_extension,
+        date_format: &ctx.config.filename_date_format,
+        separator: &ctx.config.filename_separator,
+        layout: ctx.config.directory_layout,
+        base_path: &ctx.config.market_data_path,
+        archive_paths: &ctx.config.archive_data_paths,
+        remote_store: ctx.remote_store.as_deref(),
+        exchange,
+        market_type,
+        stream,
+        symbol,
+        time_slice: &unbounded,
+        scan_cache: &ctx.scan_cache,
+        include_boundary_files: ctx.config.include_boundary_files,
+        scan_depth: ctx.config.scan_depth,
+        refresh,
+    };
+    file_finder.all_file_metas().await
+}
+
+/// Returns the newest `last` messages of the symbols newest first, independent of any time range.
+///
+/// The files are read from the newest date backwards, until enough messages are read and
+/// all files of the date are read, as files of the same date interleave in time.
+async fn last_messages(
+    ctx: &Extension<ApiContext>,
+    exchange: &str,
+    market_type: &str,
+    stream: &str,
+    symbols: &[String],
+    last: usize,
+    query: &QueryParams,
+) -> Result<Response, Error> {
+    let fields = query.field_selection().map_err(Error::BadRequest)?;
+    let batch_size = query.record_batch_size(&ctx.config).map_err(Error::BadRequest)?;
+
+    let mut files = Vec::new();
+    for symbol in symbols {
+        match symbol_files(ctx, exchange, market_type, stream, symbol, query.refresh).await {
+            Ok(symbol_files) => files.extend(symbol_files.iter().cloned().map(|file_meta| (file_meta, symbol.as_str()))),
+            // Skip a missing symbol of a basket rather than failing the whole request
+            Err(err) if err.is::<SymbolNotFound>() && symbols.len() > 1 => {
+                tracing::warn!("Skipping symbol {}: {}", symbol, err);
+            }
+            Err(err) if err.is::<SymbolNotFound>() => return Err(Error::DataNotFound(err.to_string())),
+            Err(err) => return Err(Error::Anyhow(err.context("Failed to find files"))),
+        }
+    }
+    files.sort_by(|(a, _), (b, _)| b.date.cmp(&a.date));
+
+    let base64_fallback = query.base64_fallback(&ctx.config);
+    let columns = query.extra_columns();
+    let mut messages = Vec::new();
+    let mut file_count = 0;
+    let mut files = files.into_iter().peekable();
+    while let Some((file_meta, symbol)) = files.next() {
+        messages.extend(read_parquet_file(ctx, &file_meta.path, symbol, base64_fallback, batch_size, &columns, None).await?);
+        file_count += 1;
+        let date_complete = !matches!(files.peek(), Some((next, _)) if next.date == file_meta.date);
+        if messages.len() >= last && date_complete {
+            break;
+        }
+    }
+
+    messages.sort_by(|a, b| b.timestamp_millis.cmp(&a.timestamp_millis));
+    messages.truncate(last);
+
+    let messages: Vec<ProjectedMessage> = messages
+        .into_iter()
+        .map(|message| {
+            let message = if query.include_meta { message.with_meta(exchange, market_type, stream) } else { message };
+            ProjectedMessage::new(message, fields, &ctx.config).with_raw_json(query.raw_json)
+        })
+        .collect();
+    Ok(Json(ApiResponse { messages, file_count, next_cursor: None, missing_dates: Vec::new() }).into_response())
+}
+
+/// Runs `FileFinder::find_file_metas` and records the latency of the directory scan.
+async fn find_files(file_finder: &FileFinder<'_>) -> anyhow::Result<Vec<FileMetadata>> {
+    let started = Instant::now();
+    let files = file_finder.find_file_metas().await;
+    metrics::histogram!(FIND_FILES_DURATION).record(started.elapsed().as_secs_f64());
+    files
+}
+
+/// Reduces `messages` to the requested page and returns the cursor of the next page, if any.
+///
+/// The cursor is the `timestamp_millis` of the first message that did not fit into the page.
+fn paginate(messages: &mut Vec<Message>, cursor: Option<i64>, offset: Option<usize>, limit: Option<usize>) -> Option<i64> {
+    if let Some(cursor) = cursor {
+        messages.retain(|msg| msg.timestamp_millis >= cursor);
+    }
+
+    if let Some(offset) = offset {
+        messages.drain(..offset.min(messages.len()));
+    }
+
+    match limit {
+        Some(limit) if messages.len() > limit => {
+            let next_cursor = messages[limit].timestamp_millis;
+            messages.truncate(limit);
+            Some(next_cursor)
+        }
+        _ => None,
+    }
+}
+
+async fn read_parquet_file(
+    ctx: &Extension<ApiContext>,
+    file_path: &PathBuf,
+    symbol: &str,
+    base64_fallback: bool,
+    batch_size: usize,
+    columns: &[String],
+    rows: Option<&Range<usize>>,
+) -> Result<Vec<Message>, Error> {
+    // The file is opened before waiting for a read, so a read is never held while waiting for a file
+    let _open_file = acquire_open_file(ctx).await.map_err(|err| {
+        tracing::error!("Error reading parquet file: {}", err);
+        Error::Overloaded
+    })?;
+    let _permit = acquire_read_permit(ctx).await.map_err(|err| {
+        tracing::error!("Error reading parquet file: {}", err);
+        Error::Overloaded
+    })?;
+    // A failed read can't be resumed, so a retry opens the file again
+    let messages = retry_transient(&ctx.config, file_path, || async {
+        let local_path = local_parquet_file(ctx, file_path).await?;
+        let reader = s9_parquet::AsyncParquetReader::new(&local_path, batch_size).await?;
+        metrics::counter!(PARQUET_FILES_OPENED).increment(1);
+
+        // The additional columns are read separately in the same row order as the entries
+        let mut column_rows = match columns {
+            [] => None,
+            columns => Some(parquet_meta::read_columns(&local_path, columns).await?.into_iter()),
+        };
+
+        // Decode the entries read so far batch by batch while the next batches are read
+        let mut entries = prefetch_entries(&ctx.config, batch_size, reader.into_entry_stream()).enumerate();
+        let mut messages = Vec::new();
+        let mut batch = Vec::with_capacity(batch_size);
+        while let Some((row, entry)) = entries.next().await {
+            let row_columns = column_rows.as_mut().and_then(Iterator::next);
+            match rows {
+                Some(rows) if row >= rows.end => break,
+                Some(rows) if row < rows.start => continue,
+                _ => {}
+            }
+            batch.push((entry?, row_columns));
+            if batch.len() >= batch_size {
+                let entries = std::mem::replace(&mut batch, Vec::with_capacity(batch_size));
+                messages.extend(decode_entries(&ctx.config, entries, symbol, base64_fallback).await?);
+            }
+        }
+        messages.extend(decode_entries(&ctx.config, batch, symbol, base64_fallback).await?);
+        Ok(messages)
+    }).await
+        .map_err(|err| {
+            tracing::error!("Error reading entries from parquet file: {}", err);
+            Error::FileReadFailed
+        })?;
     Ok(messages)
 }
 
+/// Decodes a batch of entries with their additional columns, on the blocking threads with `decode_blocking`.
+///
+/// Messages that fail to decode are logged and skipped.
+async fn decode_entries(
+    config: &Config,
+    entries: Vec<(s9_parquet::Entry, Option<BTreeMap<String, serde_json::Value>>)>,
+    symbol: &str,
+    base64_fallback: bool,
+) -> Result<Vec<Message>, BoxError> {
+    let symbol = symbol.to_string();
+    let decode = move || {
+        entries
+            .into_iter()
+            .filter_map(|(entry, columns)| {
+                let mut message = decode_entry(entry, &symbol, base64_fallback).ok()?;
+                message.columns = columns;
+                Some(message)
+            })
+            .collect::<Vec<_>>()
+    };
+    if config.decode_blocking {
+        Ok(tokio::task::spawn_blocking(decode).await?)
+    } else {
+        Ok(decode())
+    }
+}
+
+/// Streams the entries of the file, which the caller counts as open until the stream is dropped,
+/// see `acquire_open_files`.
 async fn stream_parquet_file(
-    parquet_reader_record_batch_size: usize,
-    file_path: &PathBuf
-) -> Result<impl Stream<Item = Result<s9_parquet::Entry, Box<dyn std::error::Error + Send + Sync>>>, StatusCode>
+    ctx: &ApiContext,
+    file_path: &PathBuf,
+    batch_size: usize,
+) -> Result<impl Stream<Item = EntryResult>, Error>
 {
-    let reader = s9_parquet::AsyncParquetReader::new(file_path, parquet_reader_record_batch_size).await
+    let permit = acquire_read_permit(ctx).await.map_err(|err| {
+        tracing::error!("Error reading parquet file: {}", err);
+        Error::Overloaded
+    })?;
+    let reader = retry_transient(&ctx.config, file_path, || async {
+        let local_path = local_parquet_file(ctx, file_path).await?;
+        Ok(s9_parquet::AsyncParquetReader::new(&local_path, batch_size).await?)
+    }).await
         .map_err(|err| {
             tracing::error!("Error reading parquet file: {}", err);
-            StatusCode::INTERNAL_SERVER_ERROR
+            Error::FileReadFailed
         })?;
+    metrics::counter!(PARQUET_FILES_OPENED).increment(1);
+    // Only opening the reader counts as read, a stream that waits for its consumer, e.g. buffered by `merge_streams`,
+    // must not hold a read that other streams need to open their files
+    drop(permit);
+
+    Ok(prefetch_entries(&ctx.config, batch_size, reader.into_entry_stream()))
+}
+
+/// Reads the entries in a separate task up to `parquet_prefetch_batches` record batches ahead of the consumer,
+/// so reading the file overlaps with decoding and sending the entries.
+///
+/// The task stops reading once the returned stream is dropped.
+fn prefetch_entries<S>(config: &Config, batch_size: usize, entries: S) -> BoxStream<'static, EntryResult>
+where
+    S: Stream<Item = EntryResult> + Send + 'static,
+{
+    let capacity = config.parquet_prefetch_batches * batch_size;
+    if capacity == 0 {
+        return entries.boxed();
+    }
+
+    let (sender, receiver) = tokio::sync::mpsc::channel(capacity);
+    tokio::spawn(async move {
+        let mut entries = std::pin::pin!(entries);
+        while let Some(entry) = entries.next().await {
+            if sender.send(entry).await.is_err() {
+                break;
+            }
+        }
+    });
+    stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|entry| (entry, receiver))
+    }).boxed()
+}
+
+/// Downloads the file from the object store into the local cache, if the market data is remote.
+async fn fetch_remote_file(ctx: &ApiContext, file_path: &PathBuf) -> Result<(), BoxError> {
+    if let Some(remote_store) = &ctx.remote_store {
+        remote_store.ensure_local(file_path).await?;
+    }
+    Ok(())
+}
+
+/// The local path to open a parquet file from, after downloading it from object storage, decompressing it
+/// and renaming its columns as needed.
+async fn local_parquet_file(ctx: &ApiContext, file_path: &PathBuf) -> Result<PathBuf, BoxError> {
+    fetch_remote_file(ctx, file_path).await?;
+    let local_path = compression::decompressed_path(file_path, &ctx.config.decompress_cache_path).await?;
+    let columns = ColumnNames {
+        timestamp: &ctx.config.parquet_timestamp_column,
+        data: &ctx.config.parquet_data_column,
+    };
+    Ok(column_names::renamed_path(&local_path, &ctx.config.decompress_cache_path, columns).await?)
+}
+
+/// Waits for a permit of the global limit of concurrent parquet reads, at most for the request timeout.
+///
+/// Taken only after the file's permit of `acquire_open_file`, so a read never waits for an open file.
+async fn acquire_read_permit(ctx: &ApiContext) -> anyhow::Result<OwnedSemaphorePermit> {
+    let timeout = Duration::from_secs(ctx.config.request_timeout_secs);
+    match tokio::time::timeout(timeout, ctx.read_permits.clone().acquire_owned()).await {
+        Ok(permit) => Ok(permit?),
+        Err(_) => Err(anyhow::anyhow!("timed out waiting for a free parquet read")),
+    }
+}
+
+/// Waits up to the request timeout for a file to be opened within `max_open_files`.
+async fn acquire_open_file(ctx: &ApiContext) -> anyhow::Result<OwnedSemaphorePermit> {
+    acquire_open_files(ctx, 1).await
+}
+
+/// Waits up to the request timeout for `count` files to be opened at once within `max_open_files`.
+///
+/// A stream needing more files than `max_open_files` reserves all of them, it opens the others anyway
+/// rather than never completing.
+async fn acquire_open_files(ctx: &ApiContext, count: usize) -> anyhow::Result<OwnedSemaphorePermit> {
+    let count = count.min(ctx.max_open_files).min(u32::MAX as usize) as u32;
+    let timeout = Duration::from_secs(ctx.config.request_timeout_secs);
+    match tokio::time::timeout(timeout, ctx.open_files.clone().acquire_many_owned(count)).await {
+        Ok(permit) => Ok(permit?),
+        Err(_) => Err(anyhow::anyhow!("timed out waiting for a free open file")),
+    }
+}
+
+/// Whether all of `max_open_files` are open, so a new stream would only queue behind the streams in flight.
+fn open_files_exhausted(ctx: &ApiContext) -> bool {
+    ctx.open_files.available_permits() == 0
+}
+
+/// Runs a parquet read, retrying IO errors with exponential backoff up to the configured attempts.
+///
+/// Any other error, e.g. of a corrupt file or schema, is permanent and returned right away.
+async fn retry_transient<T, F, Fut>(config: &Config, file_path: &PathBuf, mut operation: F) -> Result<T, BoxError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, BoxError>>,
+{
+    let max_attempts = config.read_retry_max_attempts.max(1);
+    let mut backoff = Duration::from_millis(config.read_retry_backoff_millis);
+    let mut attempt = 1;
+    loop {
+        match operation().await {
+            Err(err) if attempt < max_attempts && is_transient(&*err) => {
+                tracing::warn!("Transient error reading {:?} (attempt {}/{}), retrying in {:?}: {}",
+                    file_path, attempt, max_attempts, backoff, err);
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Whether the error is caused by an IO error that may succeed on retry, e.g. of networked storage.
+fn is_transient(err: &(dyn std::error::Error + 'static)) -> bool {
+    let mut source = Some(err);
+    while let Some(err) = source {
+        if let Some(io_err) = err.downcast_ref::<std::io::Error>() {
+            return !matches!(io_err.kind(),
+                ErrorKind::NotFound | ErrorKind::PermissionDenied | ErrorKind::InvalidData | ErrorKind::UnexpectedEof);
+        }
+        source = err.source();
+    }
+    false
+}
+
+/// Buffers all entries of a file stream and yields them in reverse order.
+///
+/// Parquet files can only be read forward, so descending order has to hold a whole file in memory
+/// instead of streaming it batch by batch. To bound that memory, a file with more than
+/// `max_entries` entries yields an error instead of being reversed.
+fn reverse_entry_stream(
+    entry_stream: impl Stream<Item = EntryResult> + Send + 'static,
+    max_entries: usize,
+) -> impl Stream<Item = EntryResult>
+{
+    stream::once(async move {
+        let mut entry_stream = Box::pin(entry_stream);
+        let mut entries = Vec::new();
+        while let Some(entry) = entry_stream.next().await {
+            if entries.len() >= max_entries {
+                let err = format!("File exceeds the descending order buffer of {} entries", max_entries);
+                return vec![Err(err.into())];
+            }
+            entries.push(entry);
+        }
+        entries.reverse();
+        entries
+    })
+    .flat_map(stream::iter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn config() -> Config {
+        Config::parse_from(["mdds"])
+    }
 
-    Ok(reader.into_entry_stream())
-}
\ No newline at end of file
+    #[test]
+    fn rejects_path_segments_leaving_the_stream_directory() {
+        let segments = [
+            "",
+            "..",
+            "../..",
+            "../etc",
+            "binance/..",
+            "...",
+            "eth..usdt",
+            "/",
+            "/etc/passwd",
+            "spot/trade",
+            "ethusdt/",
+            "\\",
+            "..\\..\\windows",
+            "c:\\windows",
+            "\0",
+            "eth\0usdt",
+            "ethusdt\0.parquet",
+        ];
+        for segment in segments {
+            assert_eq!(validate_path_segment(segment), Err(format!("invalid path segment {:?}", segment)));
+        }
+    }
+
+    #[test]
+    fn accepts_plain_path_segments() {
+        for segment in ["binance", "spot", "trade", "ethusdt", "ETH-USDT", "book_ticker", "depth20@100ms", "eth.usdt"] {
+            assert_eq!(validate_path_segment(segment), Ok(()), "{:?}", segment);
+        }
+    }
+
+    #[test]
+    fn rejects_stream_paths_with_any_invalid_segment() {
+        let config = config();
+        for (exchange, market_type, stream) in [("..", "spot", "trade"), ("binance", "../..", "trade"), ("binance", "spot", "trade/.."), ("binance", "spot", "trade\0")] {
+            assert!(validate_stream_path(&config, exchange, market_type, stream).is_err(), "{}/{}/{}", exchange, market_type, stream);
+        }
+        assert_eq!(validate_stream_path(&config, "binance", "spot", "trade"), Ok(()));
+    }
+
+    #[test]
+    fn rejects_exchanges_and_streams_outside_the_allow_lists() {
+        let mut config = config();
+        config.allowed_exchanges = vec!["binance".to_string()];
+        config.allowed_streams = vec!["trade".to_string(), "depth".to_string()];
+
+        assert_eq!(validate_stream_path(&config, "binance", "spot", "trade"), Ok(()));
+        assert_eq!(validate_stream_path(&config, "binance", "futures", "depth"), Ok(()));
+        assert_eq!(validate_stream_path(&config, "kraken", "spot", "trade"), Err("exchange kraken is not allowed".to_string()));
+        assert_eq!(validate_stream_path(&config, "Binance", "spot", "trade"), Err("exchange Binance is not allowed".to_string()));
+        assert_eq!(validate_stream_path(&config, "binance", "spot", "ticker"), Err("stream ticker is not allowed".to_string()));
+        // Traversal is rejected before the allow lists are checked
+        assert_eq!(validate_stream_path(&config, "..", "spot", "trade"), Err("invalid path segment \"..\"".to_string()));
+    }
+
+    fn time(hour: u32, minute: u32, second: u32, micros: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2019, 4, 5, hour, minute, second).unwrap() + TimeDelta::microseconds(micros.into())
+    }
+
+    /// Whether the filter contains a message with the timestamps of the given time.
+    fn contains(time_filter: &TimeFilter, time: DateTime<Utc>) -> bool {
+        time_filter.contains_message(&Message {
+            exchange: None,
+            market_type: None,
+            stream: None,
+            symbol: "ethusdt".to_string(),
+            timestamp_millis: time.timestamp_millis(),
+            timestamp_sec: time.timestamp(),
+            timestamp_sub_sec: time.timestamp_subsec_nanos() as i32,
+            data: "{}".to_string(),
+            encoding: DataEncoding::Utf8,
+            columns: None,
+        })
+    }
+
+    #[test]
+    fn time_filter_includes_from_and_excludes_to() {
+        let time_filter = TimeFilter::new(time(0, 0, 0, 0), time(1, 0, 0, 0), false);
+        assert!(!contains(&time_filter, time(0, 0, 0, 0) - TimeDelta::milliseconds(1)));
+        assert!(contains(&time_filter, time(0, 0, 0, 0)));
+        assert!(contains(&time_filter, time(0, 59, 59, 999_000)));
+        assert!(!contains(&time_filter, time(1, 0, 0, 0)));
+    }
+
+    #[test]
+    fn time_filter_includes_to_with_inclusive_end() {
+        let time_filter = TimeFilter::new(time(0, 0, 0, 0), time(1, 0, 0, 0), true);
+        assert!(contains(&time_filter, time(0, 0, 0, 0)));
+        assert!(contains(&time_filter, time(1, 0, 0, 0)));
+        assert!(!contains(&time_filter, time(1, 0, 0, 1_000)));
+    }
+
+    #[test]
+    fn time_filter_compares_whole_milliseconds_of_millisecond_bounds() {
+        // Sub-millisecond digits of the messages are ignored, they count as their millisecond
+        let time_filter = TimeFilter::new(time(0, 0, 0, 0), time(1, 0, 0, 0), false);
+        assert!(contains(&time_filter, time(0, 0, 0, 999)));
+        assert!(!contains(&time_filter, time(1, 0, 0, 999)));
+
+        let time_filter = TimeFilter::new(time(0, 0, 0, 0), time(1, 0, 0, 0), true);
+        assert!(contains(&time_filter, time(1, 0, 0, 999)));
+    }
+
+    #[test]
+    fn time_filter_slices_within_the_boundary_millisecond_of_sub_millisecond_bounds() {
+        let time_filter = TimeFilter::new(time(0, 0, 0, 500), time(1, 0, 0, 250), false);
+        assert!(!contains(&time_filter, time(0, 0, 0, 499)));
+        assert!(contains(&time_filter, time(0, 0, 0, 500)));
+        assert!(contains(&time_filter, time(1, 0, 0, 249)));
+        assert!(!contains(&time_filter, time(1, 0, 0, 250)));
+
+        let time_filter = TimeFilter::new(time(0, 0, 0, 500), time(1, 0, 0, 250), true);
+        assert!(contains(&time_filter, time(1, 0, 0, 250)));
+        assert!(!contains(&time_filter, time(1, 0, 0, 251)));
+    }
+
+    #[test]
+    fn time_filter_covers_millis_up_to_to() {
+        let (from, to) = (time(0, 0, 0, 0), time(1, 0, 0, 0));
+        let time_filter = TimeFilter::new(from, to, false);
+        assert!(time_filter.covers_millis(from.timestamp_millis(), to.timestamp_millis() - 1));
+        assert!(!time_filter.covers_millis(from.timestamp_millis(), to.timestamp_millis()));
+        assert!(!time_filter.covers_millis(from.timestamp_millis() - 1, to.timestamp_millis() - 1));
+        assert!(TimeFilter::new(from, to, true).covers_millis(from.timestamp_millis(), to.timestamp_millis()));
+    }
+}
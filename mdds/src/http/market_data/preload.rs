@@ -0,0 +1,141 @@
+use crate::config::DirectoryLayout;
+use crate::fs::symbol_scanner::SymbolScanner;
+use crate::fs::FileMetadata;
+use crate::http::ApiContext;
+use futures::{stream, StreamExt};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tokio::io::AsyncReadExt;
+
+/// The files read at once while preloading, few enough to leave the disk to the first requests.
+const PRELOAD_CONCURRENCY: usize = 4;
+
+/// Reads the files of the newest `preload_latest_days` dates of every symbol once, so the OS page cache
+/// holds them before the first queries. Runs in the background while the server already accepts requests.
+pub async fn preload_latest_days(ctx: ApiContext) {
+    let days = ctx.config.preload_latest_days as usize;
+    if ctx.remote_store.is_some() {
+        tracing::info!("Skipping preload, the market data is in object storage");
+        return;
+    }
+
+    let started = Instant::now();
+    let files = match latest_files(&ctx, days).await {
+        Ok(files) => files,
+        Err(err) => {
+            tracing::warn!("Error scanning files to preload: {}", err);
+            return;
+        }
+    };
+    tracing::info!("Preloading {} files of the latest {} days", files.len(), days);
+
+    let total = files.len();
+    let mut preloaded = 0;
+    let mut bytes = 0;
+    let mut reads = stream::iter(files)
+        .map(|path| async move { (preload_file(&path).await, path) })
+        .buffer_unordered(PRELOAD_CONCURRENCY);
+    while let Some((result, path)) = reads.next().await {
+        match result {
+            Ok(size) => {
+                preloaded += 1;
+                bytes += size;
+                if preloaded % 100 == 0 {
+                    tracing::info!("Preloaded {} of {} files", preloaded, total);
+                }
+            }
+            Err(err) => tracing::warn!("Error preloading {:?}: {}", path, err),
+        }
+    }
+    tracing::info!("Preloaded {} files with {} bytes in {:?}", preloaded, bytes, started.elapsed());
+}
+
+/// The files of the newest `days` dates of each symbol of all streams.
+async fn latest_files(ctx: &ApiContext, days: usize) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for (exchange, market_type, stream) in streams(ctx).await? {
+        let symbol_scanner = SymbolScanner {
+            parquet_file_extension: &ctx.config.parquet_file_extension,
+            date_format: &ctx.config.filename_date_format,
+            separator: &ctx.config.filename_separator,
+            layout: ctx.config.directory_layout,
+            base_path: &ctx.config.market_data_path,
+            archive_paths: &ctx.config.archive_data_paths,
+            remote_store: None,
+            exchange: &exchange,
+            market_type: &market_type,
+            stream: &stream,
+        };
+        for file_metas in symbol_scanner.files_by_symbol().await?.values() {
+            files.extend(newest_dates(file_metas, days));
+        }
+    }
+    Ok(files)
+}
+
+/// The files of the newest `days` dates among files sorted by date, a date may have several files.
+fn newest_dates(file_metas: &[FileMetadata], days: usize) -> impl Iterator<Item = PathBuf> + '_ {
+    let dates: BTreeSet<_> = file_metas.iter().map(|file_meta| file_meta.date).collect();
+    let oldest = dates.iter().rev().take(days).last().copied();
+    file_metas
+        .iter()
+        .filter(move |file_meta| oldest.is_some_and(|oldest| file_meta.date >= oldest))
+        .map(|file_meta| file_meta.path.clone())
+}
+
+/// The exchanges, market types and streams of the market data, from the directories or the
+/// file name prefixes of the flat layout.
+async fn streams(ctx: &ApiContext) -> anyhow::Result<BTreeSet<(String, String, String)>> {
+    let base_path = &ctx.config.market_data_path;
+    let mut streams = BTreeSet::new();
+    match ctx.config.directory_layout {
+        DirectoryLayout::Nested => {
+            for exchange in subdirectories(Path::new(base_path)).await? {
+                for market_type in subdirectories(&Path::new(base_path).join(&exchange)).await? {
+                    let market_type_path = Path::new(base_path).join(&exchange).join(&market_type);
+                    for stream in subdirectories(&market_type_path).await? {
+                        streams.insert((exchange.clone(), market_type.clone(), stream));
+                    }
+                }
+            }
+        }
+        DirectoryLayout::Flat => {
+            let separator = &ctx.config.filename_separator;
+            let mut entries = tokio::fs::read_dir(base_path).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let file_name = entry.file_name().to_string_lossy().to_string();
+                let mut parts = file_name.splitn(4, separator.as_str());
+                if let (Some(exchange), Some(market_type), Some(stream), Some(_)) = (parts.next(), parts.next(), parts.next(), parts.next()) {
+                    streams.insert((exchange.to_string(), market_type.to_string(), stream.to_string()));
+                }
+            }
+        }
+    }
+    Ok(streams)
+}
+
+async fn subdirectories(path: &Path) -> anyhow::Result<Vec<String>> {
+    let mut subdirectories = Vec::new();
+    let mut entries = tokio::fs::read_dir(path).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        if entry.file_type().await?.is_dir() {
+            subdirectories.push(entry.file_name().to_string_lossy().to_string());
+        }
+    }
+    Ok(subdirectories)
+}
+
+/// Reads a file once and discards its contents, returning its size.
+async fn preload_file(path: &Path) -> std::io::Result<u64> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut buffer = vec![0; 1024 * 1024];
+    let mut size = 0;
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            return Ok(size);
+        }
+        size += read as u64;
+    }
+}
@@ -0,0 +1,72 @@
+use crate::fs::column_names::READER_TIMESTAMP_COLUMN;
+use crate::fs::parquet_meta;
+use crate::fs::{FileMetadata, SymbolNotFound};
+use crate::http::market_data::{local_parquet_file, symbol_files, validate_path_segment, validate_stream_path};
+use crate::http::metrics::HTTP_REQUESTS;
+use crate::http::{ApiContext, Error};
+use axum::extract::Path;
+use axum::{Extension, Json};
+use chrono::NaiveDate;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct TimeRangeResponse {
+    symbol: String,
+    /// The date of the oldest file, which the minimum is read from.
+    first_date: NaiveDate,
+    /// The date of the newest file, which the maximum is read from.
+    last_date: NaiveDate,
+    file_count: usize,
+    /// The earliest `timestamp_millis` of the symbol, absent if the oldest file has no rows.
+    min_timestamp_millis: Option<i64>,
+    /// The latest `timestamp_millis` of the symbol, absent if the newest file has no rows.
+    max_timestamp_millis: Option<i64>,
+}
+
+/// Returns the earliest and latest message timestamps of a symbol, to clamp `from`/`to` to the available data.
+///
+/// Only the oldest and newest file are read, by their footer statistics or otherwise their timestamp column.
+pub async fn get_time_range(
+    ctx: Extension<ApiContext>,
+    Path((exchange, market_type, stream, symbol)): Path<(String, String, String, String)>,
+) -> Result<Json<TimeRangeResponse>, Error>
+{
+    metrics::counter!(HTTP_REQUESTS, "route" => "/api/v1/market-data/range").increment(1);
+    let symbol = ctx.symbol_aliases.resolve(&symbol).to_string();
+
+    validate_stream_path(&ctx.config, &exchange, &market_type, &stream)
+        .and_then(|_| validate_path_segment(&symbol))
+        .map_err(Error::BadRequest)?;
+
+    let files = match symbol_files(&ctx, &exchange, &market_type, &stream, &symbol, false).await {
+        Ok(files) => files,
+        Err(err) if err.is::<SymbolNotFound>() => return Err(Error::DataNotFound(err.to_string())),
+        Err(err) => return Err(Error::Anyhow(err.context("Failed to find files"))),
+    };
+    let (Some(first_file), Some(last_file)) = (files.first(), files.last()) else {
+        return Err(Error::DataNotFound(format!("No files found for symbol: {}", symbol)));
+    };
+
+    let first_range = file_timestamp_range(&ctx, first_file).await?;
+    let last_range = if last_file.path == first_file.path { first_range } else { file_timestamp_range(&ctx, last_file).await? };
+
+    Ok(Json(TimeRangeResponse {
+        first_date: first_file.date,
+        last_date: last_file.date,
+        file_count: files.len(),
+        min_timestamp_millis: first_range.map(|(min, _)| min),
+        max_timestamp_millis: last_range.map(|(_, max)| max),
+        symbol,
+    }))
+}
+
+async fn file_timestamp_range(ctx: &ApiContext, file_meta: &FileMetadata) -> Result<Option<(i64, i64)>, Error> {
+    async {
+        let local_path = local_parquet_file(ctx, &file_meta.path).await.map_err(|err| anyhow::anyhow!(err))?;
+        // The local copy has the columns of the reader
+        parquet_meta::file_timestamp_range(&local_path, READER_TIMESTAMP_COLUMN).await
+    }.await.map_err(|err| {
+        tracing::error!("Error reading timestamps of {:?}: {}", file_meta.path, err);
+        Error::FileReadFailed
+    })
+}
@@ -0,0 +1,118 @@
+use crate::codec::Message;
+use crate::http::market_data::fields::{FieldSelection, ProjectedMessage};
+use crate::http::market_data::{acquire_open_files, merge_streams, open_files_exhausted, s_market_data, validate_path_segment, validate_stream_path, validate_time_range, OpenFiles, Order, PendingStream, QueryParams};
+use crate::http::metrics::{ActiveStreamGuard, HTTP_REQUESTS};
+use crate::http::{ApiContext, Error};
+use axum::body::Body;
+use axum::extract::{Path, Query};
+use axum::response::{IntoResponse, Response};
+use axum::{Extension, Json};
+use axum_streams::StreamBodyAs;
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures::{FutureExt, StreamExt};
+use http::Uri;
+use serde::Deserialize;
+use std::sync::Arc;
+
+/// The files of a source a replay opens at once, those of a day and the day after.
+const OPEN_FILES_PER_SOURCE: usize = 2;
+
+/// The streams to replay, interleaved by event time as if watching the live tape.
+#[derive(Debug, Deserialize)]
+pub struct ReplayRequest {
+    sources: Vec<ReplaySource>,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    /// Include messages exactly at `to`, by default the time range ends right before it.
+    #[serde(default)]
+    inclusive_end: bool,
+}
+
+/// A single symbol of a stream to replay, its messages are tagged with it.
+#[derive(Debug, Deserialize)]
+pub struct ReplaySource {
+    exchange: String,
+    market_type: String,
+    stream: String,
+    symbol: String,
+}
+
+/// Streams the messages of all sources as newline-delimited JSON in a single timeline by `timestamp_millis`,
+/// each tagged with its exchange, market type, stream and symbol.
+///
+/// Each source is read like the streaming endpoint, with the next message of every source buffered for the merge.
+/// The files of all sources are reserved at once, so a replay never waits for files while holding some.
+pub async fn replay_market_data(
+    ctx: Extension<ApiContext>,
+    Json(request): Json<ReplayRequest>,
+) -> Result<Response, Error>
+{
+    metrics::counter!(HTTP_REQUESTS, "route" => "/stream/v1/market-data/replay").increment(1);
+
+    // A replay of more sources could never reserve their files within `max_open_files`
+    let max_sources = ctx.config.max_batch_queries.min((ctx.max_open_files / OPEN_FILES_PER_SOURCE).max(1));
+    if request.sources.is_empty() || request.sources.len() > max_sources {
+        return Err(Error::BadRequest(format!("a replay must have between 1 and {} sources", max_sources)));
+    }
+    for source in &request.sources {
+        validate_stream_path(&ctx.config, &source.exchange, &source.market_type, &source.stream)
+            .and_then(|_| validate_path_segment(&source.symbol))
+            .map_err(Error::BadRequest)?;
+    }
+    validate_time_range(&request.from, &request.to, ctx.config.max_query_range_days).map_err(Error::BadRequest)?;
+    if open_files_exhausted(&ctx) {
+        return Err(Error::Overloaded);
+    }
+
+    let from = request.from.to_rfc3339_opts(SecondsFormat::AutoSi, true);
+    let to = request.to.to_rfc3339_opts(SecondsFormat::AutoSi, true);
+    // The query of the streaming endpoint, with the metadata identifying the source of each message
+    let uri: Uri = format!("/?from={}&to={}&inclusive_end={}&include_meta=true", from, to, request.inclusive_end)
+        .parse()
+        .map_err(|_| Error::BadRequest("invalid from/to".to_string()))?;
+
+    let open_files = acquire_open_files(&ctx, OPEN_FILES_PER_SOURCE * request.sources.len()).await.map_err(|err| {
+        tracing::error!("Error reading parquet file: {}", err);
+        Error::Overloaded
+    })?;
+
+    let mut sources: Vec<PendingStream<Message>> = Vec::with_capacity(request.sources.len());
+    for source in request.sources {
+        let Query(mut query) = Query::<QueryParams>::try_from_uri(&uri).map_err(|err| Error::BadRequest(err.body_text()))?;
+        query.resolve_time_range().map_err(Error::BadRequest)?;
+        let symbol = ctx.symbol_aliases.resolve(&source.symbol).to_string();
+        let ctx = ctx.clone();
+        let path = Path((source.exchange, source.market_type, source.stream, symbol));
+        let open = async move {
+            let (_, messages) = s_market_data(ctx, path, Query(query), OpenFiles::Reserved).await;
+            messages.boxed()
+        };
+        // All sources share the date, so all of them are opened at once
+        sources.push((request.from.date_naive(), open.boxed()));
+    }
+
+    let config = Arc::clone(&ctx.config);
+    let messages = merge_streams(sources, Order::Asc, |message: &Message| (message.timestamp_millis, message.timestamp_sub_sec))
+        .map(move |result| result
+            .map(|message| ProjectedMessage::new(message, FieldSelection::all(), &config))
+            .map_err(Error::Anyhow));
+
+    // The files count as open until the body is dropped
+    let guard = ActiveStreamGuard::new(&ctx.active_streams);
+    let body = StreamBodyAs::json_nl_with_errors(messages).into_response().into_body();
+    let chunks = body.into_data_stream().map(move |chunk| {
+        let _guard = &guard;
+        let _open_files = &open_files;
+        chunk
+    });
+
+    Ok(Response::builder()
+        .status(200)
+        .header("content-type", "application/json")
+        .header("cache-control", "no-cache")
+        .header("x-accel-buffering", "no") // Disable nginx buffering if behind nginx
+        .header("x-query-from", from)
+        .header("x-query-to", to)
+        .body(Body::from_stream(chunks))
+        .unwrap())
+}
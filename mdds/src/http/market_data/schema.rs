@@ -0,0 +1,48 @@
+use crate::fs::parquet_meta::{self, FileSchema};
+use crate::fs::SymbolNotFound;
+use crate::http::market_data::{latest_file, local_parquet_file, validate_path_segment, validate_stream_path};
+use crate::http::metrics::HTTP_REQUESTS;
+use crate::http::{ApiContext, Error};
+use axum::extract::Path;
+use axum::{Extension, Json};
+use chrono::NaiveDate;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct SchemaResponse {
+    /// The date of the newest file, which the schema is read from.
+    date: NaiveDate,
+    #[serde(flatten)]
+    schema: FileSchema,
+}
+
+/// Returns the parquet schema of the newest file of a symbol, read from the footer only.
+pub async fn get_schema(
+    ctx: Extension<ApiContext>,
+    Path((exchange, market_type, stream, symbol)): Path<(String, String, String, String)>,
+) -> Result<Json<SchemaResponse>, Error>
+{
+    metrics::counter!(HTTP_REQUESTS, "route" => "/api/v1/market-data/schema").increment(1);
+    let symbol = ctx.symbol_aliases.resolve(&symbol).to_string();
+
+    validate_stream_path(&ctx.config, &exchange, &market_type, &stream)
+        .and_then(|_| validate_path_segment(&symbol))
+        .map_err(Error::BadRequest)?;
+
+    let file_meta = match latest_file(&ctx, &exchange, &market_type, &stream, &symbol, false).await {
+        Ok(Some(file_meta)) => file_meta,
+        Ok(None) => return Err(Error::DataNotFound(format!("No files found for symbol: {}", symbol))),
+        Err(err) if err.is::<SymbolNotFound>() => return Err(Error::DataNotFound(err.to_string())),
+        Err(err) => return Err(Error::Anyhow(err.context("Failed to find files"))),
+    };
+
+    let schema = async {
+        let local_path = local_parquet_file(&ctx, &file_meta.path).await.map_err(|err| anyhow::anyhow!(err))?;
+        parquet_meta::file_schema(&local_path).await
+    }.await.map_err(|err| {
+        tracing::error!("Error reading schema of {:?}: {}", file_meta.path, err);
+        Error::FileReadFailed
+    })?;
+
+    Ok(Json(SchemaResponse { date: file_meta.date, schema }))
+}
@@ -0,0 +1,168 @@
+use crate::fs::scan_cache::{FilesBySymbol, StreamKey};
+use crate::fs::symbol_scanner::SymbolScanner;
+use crate::fs::StreamNotFound;
+use crate::http::metrics::HTTP_REQUESTS;
+use crate::http::market_data::{etag_matches, validate_stream_path};
+use crate::http::{ApiContext, Error};
+use axum::extract::{Path, Query};
+use axum::response::{IntoResponse, Response};
+use axum::{Extension, Json};
+use chrono::{NaiveDate, Utc};
+use http::header::{CONTENT_TYPE, ETAG};
+use http::{HeaderMap, HeaderValue, StatusCode};
+use serde::{Deserialize, Serialize, Serializer};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// The date range of the files available for a symbol.
+#[derive(Debug, Serialize)]
+pub struct SymbolSummary {
+    pub from_date: NaiveDate,
+    pub to_date: NaiveDate,
+    pub file_count: usize,
+}
+
+/// How far the files of a symbol lag behind the current day.
+#[derive(Debug, Serialize)]
+pub struct SymbolFreshness {
+    pub latest_date: NaiveDate,
+    /// The days between the newest file and the current UTC day, `0` if the symbol has a file of today.
+    pub days_behind: i64,
+    pub file_count: usize,
+}
+
+/// The freshness of all symbols of a stream, serialized as object in the order of the symbols,
+/// the most stalled first.
+#[derive(Debug)]
+pub struct FreshnessResponse(Vec<(String, SymbolFreshness)>);
+
+impl Serialize for FreshnessResponse {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().map(|(symbol, freshness)| (symbol, freshness)))
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct SymbolsQuery {
+    /// Re-scan the stream directory even if the cached symbols have not expired yet.
+    #[serde(default)]
+    refresh: bool,
+}
+
+/// Lists the symbols of a stream with the date ranges of their files.
+///
+/// The scan is cached for `symbol_cache_ttl_secs` and the response carries an ETag, so pollers
+/// get a `304 Not Modified` until a symbol or file is added.
+pub async fn list_symbols(
+    ctx: Extension<ApiContext>,
+    Path((exchange, market_type, stream)): Path<(String, String, String)>,
+    Query(query): Query<SymbolsQuery>,
+    headers: HeaderMap,
+) -> Result<Response, Error>
+{
+    metrics::counter!(HTTP_REQUESTS, "route" => "/api/v1/market-data/symbols").increment(1);
+
+    if let Err(message) = validate_stream_path(&ctx.config, &exchange, &market_type, &stream) {
+        return Err(Error::BadRequest(message));
+    }
+    let files_by_symbol = cached_files_by_symbol(&ctx, &exchange, &market_type, &stream, query.refresh).await?;
+
+    let summaries: BTreeMap<&String, SymbolSummary> = files_by_symbol
+        .iter()
+        .filter_map(|(symbol, file_metas)| {
+            let summary = SymbolSummary {
+                from_date: file_metas.first()?.date,
+                to_date: file_metas.last()?.date,
+                file_count: file_metas.len(),
+            };
+            Some((symbol, summary))
+        })
+        .collect();
+
+    // The summaries change with every ingested file, so they are their own version
+    let body = serde_json::to_vec(&summaries).map_err(|err| Error::Anyhow(err.into()))?;
+    let etag = HeaderValue::from_str(&format!("\"{:x}\"", Sha256::digest(&body))).ok();
+    if let Some(etag) = &etag {
+        if etag_matches(&headers, etag) {
+            return Ok((StatusCode::NOT_MODIFIED, [(ETAG, etag.clone())]).into_response());
+        }
+    }
+
+    let mut response = ([(CONTENT_TYPE, "application/json")], body).into_response();
+    if let Some(etag) = etag {
+        response.headers_mut().insert(ETAG, etag);
+    }
+    Ok(response)
+}
+
+/// Lists the newest file date of each symbol of a stream and how many days it lags behind today,
+/// the most stalled symbols first, to alert on symbols that stopped being ingested.
+pub async fn symbol_freshness(
+    ctx: Extension<ApiContext>,
+    Path((exchange, market_type, stream)): Path<(String, String, String)>,
+    Query(query): Query<SymbolsQuery>,
+) -> Result<Json<FreshnessResponse>, Error>
+{
+    metrics::counter!(HTTP_REQUESTS, "route" => "/api/v1/market-data/freshness").increment(1);
+
+    if let Err(message) = validate_stream_path(&ctx.config, &exchange, &market_type, &stream) {
+        return Err(Error::BadRequest(message));
+    }
+    let files_by_symbol = cached_files_by_symbol(&ctx, &exchange, &market_type, &stream, query.refresh).await?;
+
+    let today = Utc::now().date_naive();
+    let mut freshness: Vec<(String, SymbolFreshness)> = files_by_symbol
+        .iter()
+        .filter_map(|(symbol, file_metas)| {
+            let latest_date = file_metas.last()?.date;
+            let freshness = SymbolFreshness {
+                latest_date,
+                days_behind: (today - latest_date).num_days().max(0),
+                file_count: file_metas.len(),
+            };
+            Some((symbol.clone(), freshness))
+        })
+        .collect();
+    // Stable, so symbols equally behind stay in alphabetical order
+    freshness.sort_by(|(_, a), (_, b)| b.days_behind.cmp(&a.days_behind));
+
+    Ok(Json(FreshnessResponse(freshness)))
+}
+
+/// The files of the stream grouped by symbol, from the symbol cache unless `refresh` is set.
+async fn cached_files_by_symbol(
+    ctx: &ApiContext,
+    exchange: &str,
+    market_type: &str,
+    stream: &str,
+    refresh: bool,
+) -> Result<Arc<FilesBySymbol>, Error> {
+    let symbol_scanner = SymbolScanner {
+        parquet_file_extension: &ctx.config.parquet_file_extension,
+        date_format: &ctx.config.filename_date_format,
+        separator: &ctx.config.filename_separator,
+        layout: ctx.config.directory_layout,
+        base_path: &ctx.config.market_data_path,
+        archive_paths: &ctx.config.archive_data_paths,
+        remote_store: ctx.remote_store.as_deref(),
+        exchange,
+        market_type,
+        stream,
+    };
+
+    let key = StreamKey {
+        exchange: exchange.to_string(),
+        market_type: market_type.to_string(),
+        stream: stream.to_string(),
+    };
+    let cached = if refresh { None } else { ctx.symbol_cache.get(&key) };
+    match cached {
+        Some(files_by_symbol) => Ok(files_by_symbol),
+        None => match symbol_scanner.files_by_symbol().await {
+            Ok(files_by_symbol) => Ok(ctx.symbol_cache.insert(key, files_by_symbol)),
+            Err(err) if err.is::<StreamNotFound>() => Err(Error::DataNotFound(err.to_string())),
+            Err(err) => Err(Error::Anyhow(err.context("Failed to scan stream"))),
+        },
+    }
+}
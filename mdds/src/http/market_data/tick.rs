@@ -0,0 +1,83 @@
+use crate::codec::Message;
+use crate::http::market_data::fields::ProjectedMessage;
+use serde::{Deserialize, Serialize};
+
+/// The fields of a trade parsed from the exchange payload in the `data` of a message.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Tick {
+    pub price: f64,
+    pub quantity: f64,
+    pub is_buyer_maker: bool,
+    pub trade_id: i64,
+}
+
+/// Parses the `data` of a message of one exchange payload format, `None` if it doesn't match.
+pub type TickParser = fn(&str) -> Option<Tick>;
+
+/// The payload parsers by exchange and stream, formats are added as they are needed.
+const TICK_PARSERS: &[(&str, &str, TickParser)] = &[
+    ("binance", "trade", parse_binance_trade),
+];
+
+/// The parser of the payloads of an exchange and stream, `None` if the format is not known.
+pub fn tick_parser(exchange: &str, stream: &str) -> Option<TickParser> {
+    TICK_PARSERS
+        .iter()
+        .find(|(parser_exchange, parser_stream, _)| *parser_exchange == exchange && *parser_stream == stream)
+        .map(|(_, _, parser)| *parser)
+}
+
+/// A message with the parsed fields of its payload instead of the raw `data`.
+#[derive(Debug, Serialize)]
+pub struct ParsedMessage {
+    pub symbol: String,
+    pub timestamp_millis: i64,
+    pub timestamp_sec: i64,
+    pub timestamp_sub_sec: i32,
+    #[serde(flatten)]
+    pub tick: Tick,
+}
+
+impl ParsedMessage {
+
+    pub fn new(message: &Message, tick: Tick) -> Self {
+        ParsedMessage {
+            symbol: message.symbol.clone(),
+            timestamp_millis: message.timestamp_millis,
+            timestamp_sec: message.timestamp_sec,
+            timestamp_sub_sec: message.timestamp_sub_sec,
+            tick,
+        }
+    }
+}
+
+/// A parsed message, or the raw message if its payload didn't parse.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum ParsedOrRawMessage {
+    Parsed(ParsedMessage),
+    Raw(ProjectedMessage),
+}
+
+/// A trade of the Binance spot `trade` stream, e.g. `{"e":"trade","t":12345,"p":"0.001","q":"100","m":true,...}`.
+#[derive(Deserialize)]
+struct BinanceTrade {
+    #[serde(rename = "t")]
+    trade_id: i64,
+    #[serde(rename = "p")]
+    price: String,
+    #[serde(rename = "q")]
+    quantity: String,
+    #[serde(rename = "m")]
+    is_buyer_maker: bool,
+}
+
+fn parse_binance_trade(data: &str) -> Option<Tick> {
+    let trade: BinanceTrade = serde_json::from_str(data).ok()?;
+    Some(Tick {
+        price: trade.price.parse().ok()?,
+        quantity: trade.quantity.parse().ok()?,
+        is_buyer_maker: trade.is_buyer_maker,
+        trade_id: trade.trade_id,
+    })
+}
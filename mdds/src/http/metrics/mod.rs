@@ -0,0 +1,83 @@
+use crate::http::ApiContext;
+use anyhow::Context;
+use axum::routing::get;
+use axum::{Extension, Router};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+
+/// Total number of requests, labelled by `route`.
+pub const HTTP_REQUESTS: &str = "mdds_http_requests_total";
+
+/// Total number of message payload bytes sent by the streaming endpoint.
+pub const STREAMED_BYTES: &str = "mdds_streamed_bytes_total";
+
+/// Total number of parquet files opened for reading.
+pub const PARQUET_FILES_OPENED: &str = "mdds_parquet_files_opened_total";
+
+/// Total number of parquet files skipped by their timestamp statistics without reading rows.
+pub const PARQUET_FILES_PRUNED: &str = "mdds_parquet_files_pruned_total";
+
+/// Total number of messages whose data could not be decoded.
+pub const DECODE_ERRORS: &str = "mdds_decode_errors_total";
+
+/// Number of streaming responses and websocket connections currently sending messages.
+pub const ACTIVE_STREAMS: &str = "mdds_active_streams";
+
+/// Latency of the directory scans to find the files of a query, in seconds.
+pub const FIND_FILES_DURATION: &str = "mdds_find_files_duration_seconds";
+
+const FIND_FILES_DURATION_BUCKETS: &[f64] = &[0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0];
+
+/// Installs the global Prometheus recorder that all `metrics` macros report to.
+///
+/// The recorder can only be installed once per process, later calls, e.g. by the router of each test,
+/// return the handle of the installed one.
+pub fn install_recorder() -> anyhow::Result<PrometheusHandle> {
+    static RECORDER: OnceLock<Result<PrometheusHandle, String>> = OnceLock::new();
+    RECORDER
+        .get_or_init(|| {
+            PrometheusBuilder::new()
+                .set_buckets_for_metric(Matcher::Full(FIND_FILES_DURATION.to_string()), FIND_FILES_DURATION_BUCKETS)
+                .and_then(PrometheusBuilder::install_recorder)
+                .map_err(|err| err.to_string())
+        })
+        .clone()
+        .map_err(anyhow::Error::msg)
+        .context("error installing metrics recorder")
+}
+
+/// Counts a streaming response or websocket connection as active until it is dropped,
+/// i.e. until the stream completed, failed or the client disconnected.
+#[derive(Debug)]
+pub struct ActiveStreamGuard {
+    active_streams: Arc<AtomicUsize>,
+}
+
+impl ActiveStreamGuard {
+
+    pub fn new(active_streams: &Arc<AtomicUsize>) -> Self {
+        let active = active_streams.fetch_add(1, Ordering::Relaxed) + 1;
+        metrics::gauge!(ACTIVE_STREAMS).set(active as f64);
+        ActiveStreamGuard { active_streams: Arc::clone(active_streams) }
+    }
+}
+
+impl Drop for ActiveStreamGuard {
+    fn drop(&mut self) {
+        let active = self.active_streams.fetch_sub(1, Ordering::Relaxed) - 1;
+        metrics::gauge!(ACTIVE_STREAMS).set(active as f64);
+    }
+}
+
+pub fn router() -> Router {
+    // Example URLs:
+    // localhost:9090/metrics
+    Router::new()
+        .route("/metrics", get(render_metrics))
+}
+
+/// Renders all metrics in the Prometheus text exposition format.
+async fn render_metrics(ctx: Extension<ApiContext>) -> String {
+    ctx.metrics.render()
+}
@@ -1,49 +1,582 @@
+mod auth;
+mod docs;
 mod error;
+mod health;
 mod market_data;
+mod metrics;
+mod tls;
+mod version;
 
 use anyhow::Context;
-use axum::{Extension, Router};
+use axum::body::Body;
+use axum::extract::Request;
+use axum::response::Response;
+use axum::{middleware, Extension, Router, ServiceExt};
 pub use error::Error;
+use metrics_exporter_prometheus::PrometheusHandle;
+use std::sync::atomic::AtomicUsize;
+use std::net::ToSocketAddrs;
+use std::future::Future;
 use std::sync::Arc;
-use tower::ServiceBuilder;
+use std::convert::Infallible;
+use tokio::sync::Semaphore;
+use tower::{Service, ServiceBuilder, ServiceExt as _};
 
 use crate::config::Config;
+use crate::fs::open_files;
+use crate::fs::remote::RemoteStore;
+use crate::fs::scan_cache::{ScanCache, SymbolCache};
+use crate::fs::symbol_aliases::SymbolAliases;
+use std::time::Duration;
+use http::header::{ACCEPT, ACCEPT_ENCODING, AUTHORIZATION, CONTENT_TYPE};
+use http::uri::PathAndQuery;
+use http::{Extensions, HeaderMap, HeaderName, HeaderValue, Method, StatusCode, Uri, Version};
+use hyper::body::Incoming;
+use hyper_util::rt::{TokioExecutor, TokioIo, TokioTimer};
+use hyper_util::server::conn::auto;
+use hyper_util::server::graceful::GracefulShutdown;
+use tower_http::compression::predicate::{DefaultPredicate, Predicate};
+use tower_http::compression::CompressionLayer;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use tower_http::normalize_path::NormalizePathLayer;
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
+use tower_http::timeout::TimeoutLayer;
 use tower_http::trace::TraceLayer;
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
+/// Read from requests, or generated if absent, and echoed in responses to correlate them with the logs.
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
 /// The core type through which handler functions can access common API state.
 /// This can be accessed by adding a parameter `Extension<ApiContext>` to a handler function's
 /// parameters.
 #[derive(Clone)]
 struct ApiContext {
     config: Arc<Config>,
+    metrics: PrometheusHandle,
+    scan_cache: Arc<ScanCache>,
+    /// The symbols of the streams, as listed by the symbols route.
+    symbol_cache: Arc<SymbolCache>,
+    /// Translates the symbols of requests to the symbols of the market data files.
+    symbol_aliases: Arc<SymbolAliases>,
+    /// Limits the parquet files read at once across all requests.
+    read_permits: Arc<Semaphore>,
+    /// Limits the files open at once across all requests, see `fs::open_files::max_open_files`.
+    open_files: Arc<Semaphore>,
+    /// The permits of `open_files`, the most files a stream can reserve at once.
+    max_open_files: usize,
+    /// The streaming responses and websocket connections in flight, see `metrics::ActiveStreamGuard`.
+    active_streams: Arc<AtomicUsize>,
+    /// The object store the market data is read from, if `market_data_path` is an `s3://` URL.
+    remote_store: Option<Arc<RemoteStore>>,
+}
+
+impl ApiContext {
+
+    fn new(config: Arc<Config>) -> anyhow::Result<Self> {
+        let max_open_files = open_files::max_open_files(&config)
+            .map_or(Semaphore::MAX_PERMITS, |max_open_files| max_open_files.clamp(1, Semaphore::MAX_PERMITS));
+        Ok(ApiContext {
+            metrics: metrics::install_recorder()?,
+            scan_cache: Arc::new(ScanCache::new(Duration::from_secs(config.scan_cache_ttl_secs))),
+            symbol_cache: Arc::new(SymbolCache::new(Duration::from_secs(config.symbol_cache_ttl_secs))),
+            symbol_aliases: Arc::new(SymbolAliases::load(config.symbol_aliases_path.as_deref())?),
+            read_permits: Arc::new(Semaphore::new(config.max_concurrent_reads.max(1))),
+            open_files: Arc::new(Semaphore::new(max_open_files)),
+            max_open_files,
+            active_streams: Arc::new(AtomicUsize::new(0)),
+            remote_store: RemoteStore::from_market_data_path(&config.market_data_path, &config.remote_cache_path)?
+                .map(Arc::new),
+            config,
+        })
+    }
 }
 
 pub async fn serve(config: Config) -> anyhow::Result<()> {
+    validate_config(&config)?;
     let arc_config = Arc::new(config);
 
-    let app = api_router().layer(
-        ServiceBuilder::new()
-            .layer(Extension(ApiContext {
-                config: Arc::clone(&arc_config),
-            }))
-            // Enables logging. Use `RUST_LOG=tower_http=debug`
-            .layer(TraceLayer::new_for_http()),
-    );
+    let api_context = ApiContext::new(Arc::clone(&arc_config))?;
+
+    // Normalized before routing, as layers of the router itself only run for matched routes
+    let lowercase_paths = arc_config.lowercase_paths;
+    let app = ServiceBuilder::new()
+        .layer(NormalizePathLayer::trim_trailing_slash())
+        .map_request(move |request: Request| if lowercase_paths { lowercase_path(request) } else { request })
+        .service(app(api_context.clone())?);
+
+    if arc_config.preload_latest_days > 0 {
+        tokio::spawn(market_data::preload::preload_latest_days(api_context.clone()));
+    }
+
+    let metrics_app = metrics::router().layer(Extension(api_context));
 
     let config = Arc::clone(&arc_config);
 
-    let listener = tokio::net::TcpListener::bind(&config.server_address).await?;
-    tracing::info!("Server listening on {}", listener.local_addr()?);
+    let tls = match (&config.tls_cert, &config.tls_key) {
+        (Some(cert_path), Some(key_path)) => Some(Arc::new(tls::Tls::load(cert_path, key_path, config.http2_enabled)?)),
+        (None, None) => None,
+        _ => anyhow::bail!("MDDS_TLS_CERT and MDDS_TLS_KEY must be configured together"),
+    };
+    if let Some(tls) = &tls {
+        tokio::spawn(tls::reload_on_sighup(Arc::clone(tls)));
+    }
+
+    let metrics_listener = tokio::net::TcpListener::bind(&config.metrics_address).await?;
+    tracing::info!("Metrics listening on {}", metrics_listener.local_addr()?);
+
+    tokio::try_join!(
+        async {
+            if let Some(socket_path) = config.server_address.strip_prefix("unix:") {
+                return serve_unix_socket(socket_path, app).await;
+            }
 
-    axum::serve(listener, app)
+            let listener = tokio::net::TcpListener::bind(&config.server_address).await?;
+            tracing::info!("Server listening on {}{}", listener.local_addr()?, if tls.is_some() { " with TLS" } else { "" });
+            serve_tcp(listener, app, &config, tls, shutdown_signal()).await
+        },
+        async {
+            axum::serve(metrics_listener, metrics_app)
+                .with_graceful_shutdown(shutdown_signal())
+                .await
+                .context("error running metrics server")
+        },
+    )?;
+
+    tracing::info!("Server shut down");
+    Ok(())
+}
+
+/// Serves the app on TCP with the HTTP/2 and keep-alive settings of the config, which `axum::serve` doesn't expose,
+/// and with TLS if configured.
+///
+/// Once `shutdown` resolves, no further connections are accepted and the open ones finish their in-flight requests and streams.
+async fn serve_tcp<S>(
+    listener: tokio::net::TcpListener,
+    app: S,
+    config: &Config,
+    tls: Option<Arc<tls::Tls>>,
+    shutdown: impl Future<Output = ()>,
+) -> anyhow::Result<()>
+where
+    S: Service<Request, Response = Response, Error = Infallible> + Clone + Send + 'static,
+    S::Future: Send,
+{
+    let mut builder = auto::Builder::new(TokioExecutor::new());
+    builder.http1()
+        .timer(TokioTimer::new())
+        .keep_alive(config.http1_keep_alive);
+    if config.keep_alive_idle_timeout_secs > 0 {
+        builder.http1().header_read_timeout(Duration::from_secs(config.keep_alive_idle_timeout_secs));
+    }
+    if config.http2_enabled {
+        builder.http2()
+            .timer(TokioTimer::new())
+            .max_concurrent_streams(config.http2_max_concurrent_streams);
+        if config.http2_keep_alive_interval_secs > 0 {
+            builder.http2().keep_alive_interval(Duration::from_secs(config.http2_keep_alive_interval_secs));
+        }
+    } else {
+        builder = builder.http1_only();
+    }
+
+    let graceful = GracefulShutdown::new();
+    let mut shutdown = std::pin::pin!(shutdown);
+    loop {
+        let stream = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                // E.g. too many open files, the listener itself is still usable
+                Err(err) => {
+                    tracing::warn!("Error accepting connection: {}", err);
+                    continue;
+                }
+            },
+            _ = &mut shutdown => break,
+        };
+        if let Err(err) = stream.set_nodelay(true) {
+            tracing::debug!("Error setting TCP_NODELAY: {}", err);
+        }
+
+        let app = app.clone();
+        let service = hyper::service::service_fn(move |request: http::Request<Incoming>| {
+            app.clone().oneshot(request.map(Body::new))
+        });
+        // Upgrades are required by the websocket route
+        let Some(tls) = tls.clone() else {
+            let connection = builder.serve_connection_with_upgrades(TokioIo::new(stream), service).into_owned();
+            let connection = graceful.watch(connection);
+            tokio::spawn(async move {
+                if let Err(err) = connection.await {
+                    tracing::debug!("Error serving connection: {}", err);
+                }
+            });
+            continue;
+        };
+
+        // The handshake runs in the connection's task, so a slow client doesn't hold up accepting others
+        let builder = builder.clone();
+        let watcher = graceful.watcher();
+        tokio::spawn(async move {
+            let stream = match tls.accept(stream).await {
+                Ok(stream) => stream,
+                Err(err) => {
+                    tracing::debug!("Error in TLS handshake: {}", err);
+                    return;
+                }
+            };
+            let connection = builder.serve_connection_with_upgrades(TokioIo::new(stream), service);
+            if let Err(err) = watcher.watch(connection).await {
+                tracing::debug!("Error serving connection: {}", err);
+            }
+        });
+    }
+
+    drop(listener);
+    graceful.shutdown().await;
+    Ok(())
+}
+
+/// Serves the app on a Unix domain socket, e.g. for sidecars, removing the socket file on shutdown.
+///
+/// A socket file left behind by a previous run would fail the bind, so it is removed first.
+#[cfg(unix)]
+async fn serve_unix_socket<S>(socket_path: &str, app: S) -> anyhow::Result<()>
+where
+    S: Service<Request, Response = Response, Error = Infallible> + Clone + Send + 'static,
+    S::Future: Send,
+{
+    use std::os::unix::fs::FileTypeExt;
+
+    match tokio::fs::symlink_metadata(socket_path).await {
+        Ok(metadata) if metadata.file_type().is_socket() => {
+            tracing::info!("Removing stale socket {}", socket_path);
+            tokio::fs::remove_file(socket_path).await
+                .with_context(|| format!("error removing stale socket {}", socket_path))?;
+        }
+        Ok(_) => anyhow::bail!("{} exists and is not a socket", socket_path),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err).with_context(|| format!("error accessing socket {}", socket_path)),
+    }
+
+    let listener = tokio::net::UnixListener::bind(socket_path)
+        .with_context(|| format!("error binding socket {}", socket_path))?;
+    tracing::info!("Server listening on unix:{}", socket_path);
+
+    let result = axum::serve(listener, ServiceExt::<Request>::into_make_service(app))
+        .with_graceful_shutdown(shutdown_signal())
         .await
-        .context("error running server")
+        .context("error running server");
+
+    if let Err(err) = tokio::fs::remove_file(socket_path).await {
+        tracing::warn!("Error removing socket {}: {}", socket_path, err);
+    }
+    result
+}
+
+#[cfg(not(unix))]
+async fn serve_unix_socket<S>(socket_path: &str, _app: S) -> anyhow::Result<()> {
+    anyhow::bail!("unix:{} requires Unix domain sockets, which this platform doesn't support", socket_path)
+}
+
+/// Lowercases the path of a request, but not its query, so e.g. `/Binance/Spot/Trade/ETHUSDT` matches
+/// the lowercase directories of the market data.
+///
+/// Symbol aliases are resolved by the handlers after this, which is safe as they match regardless of case.
+fn lowercase_path(mut request: Request) -> Request {
+    let uri = request.uri();
+    if !uri.path().bytes().any(|byte| byte.is_ascii_uppercase()) {
+        return request;
+    }
+
+    let path_and_query = match uri.query() {
+        Some(query) => format!("{}?{}", uri.path().to_ascii_lowercase(), query),
+        None => uri.path().to_ascii_lowercase(),
+    };
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = PathAndQuery::try_from(path_and_query).ok();
+    if let Ok(uri) = Uri::from_parts(parts) {
+        *request.uri_mut() = uri;
+    }
+    request
+}
+
+/// Resolves on SIGINT or SIGTERM, after which the servers stop accepting connections
+/// and wait for in-flight requests and streams to complete.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install SIGINT handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+
+    tracing::info!("Shutdown signal received, draining in-flight requests");
 }
 
-fn api_router() -> Router {
+/// Fails startup on a configuration that would only fail requests later, e.g. of a volume that isn't mounted.
+fn validate_config(config: &Config) -> anyhow::Result<()> {
+    // Object storage is checked by listing it per request, there is no directory to check
+    if !config.market_data_path.starts_with("s3://") {
+        match std::fs::metadata(&config.market_data_path) {
+            Ok(metadata) if metadata.is_dir() => {}
+            Ok(_) => anyhow::bail!("MDDS_MARKET_DATA_PATH {} is not a directory", config.market_data_path),
+            Err(err) => anyhow::bail!("MDDS_MARKET_DATA_PATH {} is not readable: {}", config.market_data_path, err),
+        }
+        std::fs::read_dir(&config.market_data_path)
+            .with_context(|| format!("MDDS_MARKET_DATA_PATH {} is not readable", config.market_data_path))?;
+    }
+    // Archives may be mounted on demand, so a missing one only leaves its dates missing
+    for archive_path in &config.archive_data_paths {
+        if !std::path::Path::new(archive_path).is_dir() {
+            tracing::warn!("MDDS_ARCHIVE_DATA_PATHS {} is not a directory", archive_path);
+        }
+    }
+
+    if config.parquet_reader_record_batch_size == 0 {
+        anyhow::bail!("MDDS_PARQUET_READER_RECORD_BATCH_SIZE must be greater than 0");
+    }
+
+    if !config.server_address.starts_with("unix:") {
+        config.server_address
+            .to_socket_addrs()
+            .with_context(|| format!("MDDS_SERVER_ADDRESS {} is not a valid address", config.server_address))?;
+    }
+    config.metrics_address
+        .to_socket_addrs()
+        .with_context(|| format!("MDDS_METRICS_ADDRESS {} is not a valid address", config.metrics_address))?;
+    Ok(())
+}
+
+/// The routes of `serve` for the given config, without listening, e.g. to send requests to them in tests.
+///
+/// Paths are taken as they are, `lowercase_paths` and trailing slashes are only handled by `serve`.
+pub fn router(config: Config) -> anyhow::Result<Router> {
+    validate_config(&config)?;
+    app(ApiContext::new(Arc::new(config))?)
+}
+
+/// The complete client facing application with all routes and layers, but not yet bound to a listener.
+///
+/// Kept apart from `serve`, so the application can be driven in-process with requests against a prepared context.
+fn app(api_context: ApiContext) -> anyhow::Result<Router> {
+    let config = Arc::clone(&api_context.config);
+
+    let app = api_router(&config)
+        // Enables logging. Use `RUST_LOG=tower_http=debug`
+        .layer(TraceLayer::new_for_http().make_span_with(request_span))
+        // Merged after the `TraceLayer` to keep frequent probes out of the logs
+        .merge(health::router())
+        .layer(
+            ServiceBuilder::new()
+                // Outermost, so the span of the `TraceLayer` and every response, errors included, carry the ID
+                .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+                .layer(PropagateRequestIdLayer::x_request_id())
+                .layer(Extension(api_context))
+                .layer(cors_layer(&config)?)
+                .layer(middleware::from_fn(auth::require_api_key)),
+        );
+
+    Ok(app)
+}
+
+/// The `X-Request-Id` of a request, as sent by the client or generated by the `SetRequestIdLayer`.
+fn request_id(headers: &HeaderMap) -> &str {
+    headers
+        .get(REQUEST_ID_HEADER)
+        .and_then(|request_id| request_id.to_str().ok())
+        .unwrap_or_default()
+}
+
+/// The span of a request, so all logs of the request and its streamed response carry the request ID.
+fn request_span<B>(request: &http::Request<B>) -> tracing::Span {
+    tracing::info_span!(
+        "request",
+        method = %request.method(),
+        uri = %request.uri(),
+        version = ?request.version(),
+        request_id = request_id(request.headers()),
+    )
+}
+
+fn api_router(config: &Config) -> Router {
     // This is the order that the modules were authored in.
-    market_data::router()
+    let router = market_data::router()
+        .merge(docs::router())
+        .merge(version::router());
     // .merge(more::router())
-}
\ No newline at end of file
+    let router = if config.debug_endpoints {
+        router.merge(market_data::debug_router())
+    } else {
+        router
+    };
+
+    // Only bounds the time until the response starts, so long streams are not cut off
+    let router = router.layer(TimeoutLayer::with_status_code(
+        StatusCode::REQUEST_TIMEOUT,
+        Duration::from_secs(config.request_timeout_secs),
+    ));
+
+    if config.compression_enabled {
+        router.layer(compression_layer())
+    } else {
+        router
+    }
+}
+
+/// Compresses responses by `Accept-Encoding: gzip` or `zstd`.
+///
+/// Streamed responses (flagged by `x-accel-buffering: no`) are left uncompressed, since the encoder
+/// would hold back messages until its buffer fills up instead of passing them on one by one.
+fn compression_layer() -> CompressionLayer<impl Predicate> {
+    let is_buffered = |_: StatusCode, _: Version, headers: &HeaderMap, _: &Extensions| {
+        !headers.contains_key("x-accel-buffering")
+    };
+
+    CompressionLayer::new().compress_when(DefaultPredicate::new().and(is_buffered))
+}
+
+/// Restricts cross-origin requests to the configured origins.
+///
+/// Without any configured origins, any origin is allowed only if explicitly configured to be
+/// permissive, otherwise cross-origin requests are denied.
+fn cors_layer(config: &Config) -> anyhow::Result<CorsLayer> {
+    let origins = config
+        .cors_allowed_origins
+        .iter()
+        .map(|origin| origin.trim())
+        .filter(|origin| !origin.is_empty())
+        .map(|origin| HeaderValue::from_str(origin).with_context(|| format!("invalid CORS origin {}", origin)))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    if !origins.is_empty() {
+        return Ok(CorsLayer::new()
+            .allow_origin(AllowOrigin::list(origins))
+            // POST for the JSON bodies of the batch query and the replay
+            .allow_methods([Method::GET, Method::HEAD, Method::POST])
+            .allow_headers([
+                ACCEPT,
+                ACCEPT_ENCODING,
+                AUTHORIZATION,
+                CONTENT_TYPE,
+                HeaderName::from_static(auth::API_KEY_HEADER),
+                HeaderName::from_static(REQUEST_ID_HEADER),
+            ])
+            .expose_headers([HeaderName::from_static(REQUEST_ID_HEADER)]));
+    }
+
+    if config.cors_permissive {
+        tracing::warn!("CORS is permissive, cross-origin requests are allowed from any origin");
+        Ok(CorsLayer::permissive())
+    } else {
+        Ok(CorsLayer::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{ArrayRef, BinaryArray, Int32Array, Int64Array};
+    use arrow::datatypes::{DataType, Field, Schema};
+    use arrow::record_batch::RecordBatch;
+    use clap::Parser;
+    use parquet::arrow::ArrowWriter;
+    use std::path::Path;
+    use std::sync::atomic::Ordering;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Writes the `ethusdt` trades of 2019-04-05 with a trade at 10:00:00 and another a second later.
+    fn write_trades(market_data_path: &Path) {
+        let directory = market_data_path.join("binance").join("spot").join("trade");
+        std::fs::create_dir_all(&directory).unwrap();
+        let millis = [1_554_458_400_000_i64, 1_554_458_401_000];
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("timestamp_millis", DataType::Int64, false),
+            Field::new("timestamp_sec", DataType::Int64, false),
+            Field::new("timestamp_sub_sec", DataType::Int32, false),
+            Field::new("data", DataType::Binary, false),
+        ]));
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(Int64Array::from_iter_values(millis)),
+            Arc::new(Int64Array::from_iter_values(millis.map(|millis| millis / 1000))),
+            Arc::new(Int32Array::from_iter_values([0, 0])),
+            Arc::new(BinaryArray::from_iter_values([r#"{"e":"trade"}"#, r#"{"e":"trade"}"#])),
+        ];
+        let batch = RecordBatch::try_new(Arc::clone(&schema), columns).unwrap();
+        let file = std::fs::File::create(directory.join("ethusdt.2019-04-05.parquet")).unwrap();
+        let mut writer = ArrowWriter::try_new(file, schema, None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+    }
+
+    /// The messages of a streamed response so far, each with its `timestamp_millis`.
+    fn message_count(response: &[u8]) -> usize {
+        String::from_utf8_lossy(response).matches("\"timestamp_millis\"").count()
+    }
+
+    #[tokio::test]
+    async fn drains_streams_on_shutdown() {
+        let dir = tempfile::tempdir().unwrap();
+        let market_data_path = dir.path().join("market_data");
+        write_trades(&market_data_path);
+        let config = Arc::new(Config::parse_from([
+            "mdds".to_string(),
+            "--market-data-path".to_string(),
+            market_data_path.to_string_lossy().to_string(),
+            "--decompress-cache-path".to_string(),
+            dir.path().join("decompress").to_string_lossy().to_string(),
+            "--remote-cache-path".to_string(),
+            dir.path().join("remote").to_string_lossy().to_string(),
+        ]));
+        let ctx = ApiContext::new(Arc::clone(&config)).unwrap();
+        let active_streams = Arc::clone(&ctx.active_streams);
+        let app = app(ctx).unwrap();
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let (shutdown, shutdown_received) = tokio::sync::oneshot::channel::<()>();
+        let server = tokio::spawn(async move {
+            serve_tcp(listener, app, &config, None, async { let _ = shutdown_received.await; }).await
+        });
+
+        // At speed 1 the second trade is only sent a second after the first one
+        let mut connection = tokio::net::TcpStream::connect(address).await.unwrap();
+        connection.write_all(concat!(
+            "GET /stream/v1/market-data/binance/spot/trade/ethusdt?from=2019-04-05T10:00:00Z&to=2019-04-05T11:00:00Z&speed=1 HTTP/1.1\r\n",
+            "host: localhost\r\n\r\n",
+        ).as_bytes()).await.unwrap();
+        let mut response = Vec::new();
+        let mut buffer = [0; 4096];
+        while message_count(&response) == 0 {
+            let read = connection.read(&mut buffer).await.unwrap();
+            assert_ne!(read, 0, "{}", String::from_utf8_lossy(&response));
+            response.extend_from_slice(&buffer[..read]);
+        }
+        assert_eq!(active_streams.load(Ordering::Relaxed), 1);
+
+        shutdown.send(()).unwrap();
+        // The connection is closed once the stream has been sent completely
+        tokio::time::timeout(Duration::from_secs(10), connection.read_to_end(&mut response)).await.unwrap().unwrap();
+        tokio::time::timeout(Duration::from_secs(10), server).await.unwrap().unwrap().unwrap();
+
+        assert!(response.starts_with(b"HTTP/1.1 200"), "{}", String::from_utf8_lossy(&response));
+        assert_eq!(message_count(&response), 2);
+        assert_eq!(active_streams.load(Ordering::Relaxed), 0);
+    }
+}
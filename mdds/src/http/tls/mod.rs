@@ -0,0 +1,102 @@
+use anyhow::Context;
+use std::io::BufReader;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+
+/// The time a client may take for the TLS handshake, so stalled handshakes don't pile up.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Terminates TLS of accepted TCP connections with the certificate and key of the config.
+///
+/// The certificate is read once at startup and again by `reload`, e.g. after a renewal, while
+/// connections already established keep the certificate they were accepted with.
+pub struct Tls {
+    cert_path: String,
+    key_path: String,
+    /// The protocols offered by ALPN, `h2` only if HTTP/2 is enabled.
+    alpn_protocols: Vec<Vec<u8>>,
+    server_config: RwLock<Arc<ServerConfig>>,
+}
+
+impl Tls {
+    pub fn load(cert_path: &str, key_path: &str, http2_enabled: bool) -> anyhow::Result<Self> {
+        let alpn_protocols = if http2_enabled {
+            vec![b"h2".to_vec(), b"http/1.1".to_vec()]
+        } else {
+            vec![b"http/1.1".to_vec()]
+        };
+        let server_config = server_config(cert_path, key_path, &alpn_protocols)?;
+        Ok(Self {
+            cert_path: cert_path.to_string(),
+            key_path: key_path.to_string(),
+            alpn_protocols,
+            server_config: RwLock::new(Arc::new(server_config)),
+        })
+    }
+
+    /// Re-reads the certificate and key, keeping the current ones if they are invalid.
+    pub fn reload(&self) -> anyhow::Result<()> {
+        let server_config = server_config(&self.cert_path, &self.key_path, &self.alpn_protocols)?;
+        *self.server_config.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = Arc::new(server_config);
+        Ok(())
+    }
+
+    pub async fn accept(&self, stream: TcpStream) -> std::io::Result<TlsStream<TcpStream>> {
+        let server_config = Arc::clone(&self.server_config.read().unwrap_or_else(|poisoned| poisoned.into_inner()));
+        tokio::time::timeout(HANDSHAKE_TIMEOUT, TlsAcceptor::from(server_config).accept(stream))
+            .await
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "TLS handshake timed out"))?
+    }
+}
+
+fn server_config(cert_path: &str, key_path: &str, alpn_protocols: &[Vec<u8>]) -> anyhow::Result<ServerConfig> {
+    let cert_file = std::fs::File::open(cert_path).with_context(|| format!("error opening TLS certificate {}", cert_path))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(cert_file))
+        .collect::<Result<Vec<CertificateDer>, _>>()
+        .with_context(|| format!("error reading TLS certificate {}", cert_path))?;
+    if certs.is_empty() {
+        anyhow::bail!("no certificate in {}", cert_path);
+    }
+
+    let key_file = std::fs::File::open(key_path).with_context(|| format!("error opening TLS key {}", key_path))?;
+    let key: PrivateKeyDer = rustls_pemfile::private_key(&mut BufReader::new(key_file))
+        .with_context(|| format!("error reading TLS key {}", key_path))?
+        .with_context(|| format!("no private key in {}", key_path))?;
+
+    // The provider is explicit, as another one may be enabled by e.g. the rustls of `reqwest`
+    let provider = Arc::new(tokio_rustls::rustls::crypto::aws_lc_rs::default_provider());
+    let mut server_config = ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .context("invalid TLS protocol versions")?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .context("invalid TLS certificate or key")?;
+    server_config.alpn_protocols = alpn_protocols.to_vec();
+    Ok(server_config)
+}
+
+/// Reloads the certificate and key on every SIGHUP until the process exits.
+#[cfg(unix)]
+pub async fn reload_on_sighup(tls: Arc<Tls>) {
+    let mut hangup = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(err) => {
+            tracing::warn!("Error installing SIGHUP handler, TLS certificate won't be reloaded: {}", err);
+            return;
+        }
+    };
+    while hangup.recv().await.is_some() {
+        match tls.reload() {
+            Ok(()) => tracing::info!("Reloaded TLS certificate {}", tls.cert_path),
+            Err(err) => tracing::warn!("Error reloading TLS certificate, keeping the current one: {:#}", err),
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub async fn reload_on_sighup(_tls: Arc<Tls>) {}
@@ -0,0 +1,57 @@
+use crate::http::ApiContext;
+use axum::routing::get;
+use axum::{Extension, Json, Router};
+use serde::Serialize;
+use std::sync::atomic::Ordering;
+
+pub fn router() -> Router {
+    // Example URL:
+    // localhost:8080/version
+    Router::new().route("/version", get(version))
+}
+
+#[derive(Debug, Serialize)]
+struct VersionResponse {
+    version: &'static str,
+    /// The commit the server was built from, embedded by `build.rs`.
+    git_commit: Option<&'static str>,
+    /// The streaming responses and websocket connections in flight, a deploy should wait for `0`.
+    active_streams: usize,
+    config: ConfigSummary,
+}
+
+/// The configuration values relevant to verify a deployment, secrets are never included.
+#[derive(Debug, Serialize)]
+struct ConfigSummary {
+    server_address: String,
+    metrics_address: String,
+    market_data_path: String,
+    parquet_file_extension: String,
+    filename_date_format: String,
+    filename_separator: String,
+    allowed_exchanges: Vec<String>,
+    allowed_streams: Vec<String>,
+    /// Whether API keys are required, the keys themselves are redacted.
+    api_keys_configured: bool,
+}
+
+/// Reports the build and the data the server is pointed at.
+async fn version(ctx: Extension<ApiContext>) -> Json<VersionResponse> {
+    let config = &ctx.config;
+    Json(VersionResponse {
+        version: env!("CARGO_PKG_VERSION"),
+        git_commit: option_env!("MDDS_GIT_COMMIT"),
+        active_streams: ctx.active_streams.load(Ordering::Relaxed),
+        config: ConfigSummary {
+            server_address: config.server_address.clone(),
+            metrics_address: config.metrics_address.clone(),
+            market_data_path: config.market_data_path.clone(),
+            parquet_file_extension: config.parquet_file_extension.clone(),
+            filename_date_format: config.filename_date_format.clone(),
+            filename_separator: config.filename_separator.clone(),
+            allowed_exchanges: config.allowed_exchanges.clone(),
+            allowed_streams: config.allowed_streams.clone(),
+            api_keys_configured: !config.api_keys.is_empty(),
+        },
+    })
+}
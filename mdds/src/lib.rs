@@ -1,22 +1,58 @@
-mod config;
+#[cfg(feature = "client")]
+pub mod client;
+pub mod codec;
+pub mod config;
 mod http;
 mod fs;
 
-use crate::config::Config;
+use crate::config::{Config, LogFormat, RuntimeFlavor};
+pub use crate::http::router;
 use anyhow::Error;
 use clap::Parser;
 
-pub async fn run() -> anyhow::Result<(), Error> {
+/// The single entrypoint of the server, called by the `main` binary.
+///
+/// Loads the configuration, builds the tokio runtime configured by it and hands over to [`http::serve`],
+/// which owns all routes and handlers.
+pub fn run() -> anyhow::Result<(), Error> {
     // Load .env file if it exists
     dotenv::dotenv().ok();
 
-    // Initialize tracing/logging
-    tracing_subscriber::fmt()
-        .with_max_level(tracing::Level::DEBUG)
-        .init();
-
     // Load configuration from environment variables
     let config = Config::parse();
 
-    http::serve(config).await
+    // Initialize tracing/logging
+    init_logging(&config);
+
+    build_runtime(&config)?.block_on(http::serve(config))
+}
+
+fn build_runtime(config: &Config) -> anyhow::Result<tokio::runtime::Runtime> {
+    let mut builder = match config.runtime_flavor {
+        RuntimeFlavor::MultiThread => {
+            let mut builder = tokio::runtime::Builder::new_multi_thread();
+            if config.worker_threads > 0 {
+                builder.worker_threads(config.worker_threads);
+            }
+            builder
+        }
+        RuntimeFlavor::CurrentThread => tokio::runtime::Builder::new_current_thread(),
+    };
+    let runtime = builder
+        .enable_all()
+        .max_blocking_threads(config.max_blocking_threads.max(1))
+        .build()?;
+    Ok(runtime)
+}
+
+fn init_logging(config: &Config) {
+    match config.log_format {
+        LogFormat::Json => tracing_subscriber::fmt()
+            .json()
+            .with_max_level(config.log_level)
+            .init(),
+        LogFormat::Text => tracing_subscriber::fmt()
+            .with_max_level(config.log_level)
+            .init(),
+    }
 }
\ No newline at end of file
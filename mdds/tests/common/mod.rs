@@ -0,0 +1,130 @@
+//! Fixture market data files in a temporary directory and requests to the router serving them.
+
+// Each test crate only uses some of the helpers
+#![allow(dead_code)]
+
+use arrow::array::{ArrayRef, BinaryArray, Int32Array, Int64Array};
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::record_batch::RecordBatch;
+use axum::body::Body;
+use axum::http::{HeaderMap, Request, StatusCode};
+use axum::Router;
+use chrono::{DateTime, TimeZone, Utc};
+use clap::Parser;
+use http_body_util::BodyExt;
+use mdds::config::Config;
+use parquet::arrow::ArrowWriter;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tempfile::TempDir;
+use tower::ServiceExt;
+
+/// A market data directory in the nested layout, e.g. `binance/spot/trade/ethusdt.2019-04-05.parquet`,
+/// removed when dropped.
+pub struct MarketData {
+    dir: TempDir,
+}
+
+impl MarketData {
+
+    pub fn new() -> Self {
+        MarketData { dir: tempfile::tempdir().unwrap() }
+    }
+
+    pub fn path(&self) -> PathBuf {
+        self.dir.path().join("market_data")
+    }
+
+    /// Writes the file of a symbol's day of a `binance/spot` stream with a message at each of the times.
+    pub fn write_day(&self, stream: &str, symbol: &str, date: &str, times: &[DateTime<Utc>]) -> PathBuf {
+        let directory = self.path().join("binance").join("spot").join(stream);
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join(format!("{}.{}.parquet", symbol, date));
+        write_parquet(&path, times);
+        path
+    }
+
+    /// The router of a server configured to serve this directory, with further arguments,
+    /// e.g. `["--debug-endpoints", "true"]`.
+    pub fn router(&self, args: &[&str]) -> Router {
+        std::fs::create_dir_all(self.path()).unwrap();
+        let cache_path = self.dir.path().join("cache");
+        let mut cli_args = vec![
+            "mdds".to_string(),
+            "--market-data-path".to_string(),
+            self.path().to_string_lossy().to_string(),
+            "--decompress-cache-path".to_string(),
+            cache_path.join("decompress").to_string_lossy().to_string(),
+            "--remote-cache-path".to_string(),
+            cache_path.join("remote").to_string_lossy().to_string(),
+        ];
+        cli_args.extend(args.iter().map(|arg| arg.to_string()));
+        mdds::router(Config::parse_from(cli_args)).unwrap()
+    }
+}
+
+/// A UTC time of the fixture days.
+pub fn time(day: u32, hour: u32, minute: u32, second: u32, millis: i64) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2019, 4, day, hour, minute, second).unwrap() + chrono::TimeDelta::milliseconds(millis)
+}
+
+/// The times of `count` trades spread evenly over a fixture day.
+pub fn trades_of_day(day: u32, count: usize) -> Vec<DateTime<Utc>> {
+    let interval_millis = 86_400_000 / count.max(1) as i64;
+    (0..count).map(|index| time(day, 0, 0, 0, index as i64 * interval_millis)).collect()
+}
+
+/// The data of the fixture message at a time, a trade of the timestamp's price.
+pub fn message_data(time: &DateTime<Utc>) -> String {
+    format!(r#"{{"e":"trade","T":{},"p":"{}.5","q":"1"}}"#, time.timestamp_millis(), time.timestamp() % 1000)
+}
+
+/// Writes a market data file with the timestamp columns and the `data` of a message at each of the times.
+pub fn write_parquet(path: &Path, times: &[DateTime<Utc>]) {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("timestamp_millis", DataType::Int64, false),
+        Field::new("timestamp_sec", DataType::Int64, false),
+        Field::new("timestamp_sub_sec", DataType::Int32, false),
+        Field::new("data", DataType::Binary, false),
+    ]));
+    let data: Vec<String> = times.iter().map(message_data).collect();
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(Int64Array::from_iter_values(times.iter().map(DateTime::timestamp_millis))),
+        Arc::new(Int64Array::from_iter_values(times.iter().map(DateTime::timestamp))),
+        Arc::new(Int32Array::from_iter_values(times.iter().map(|time| time.timestamp_subsec_nanos() as i32))),
+        Arc::new(BinaryArray::from_iter_values(data.iter().map(String::as_bytes))),
+    ];
+    let batch = RecordBatch::try_new(Arc::clone(&schema), columns).unwrap();
+
+    let mut writer = ArrowWriter::try_new(File::create(path).unwrap(), schema, None).unwrap();
+    writer.write(&batch).unwrap();
+    writer.close().unwrap();
+}
+
+/// Sends a `GET` request to the router and returns the response with its whole body.
+pub async fn get(router: &Router, uri: &str) -> (StatusCode, HeaderMap, Vec<u8>) {
+    let request = Request::get(uri).body(Body::empty()).unwrap();
+    let response = router.clone().oneshot(request).await.unwrap();
+    let (parts, body) = response.into_parts();
+    let body = body.collect().await.unwrap().to_bytes();
+    (parts.status, parts.headers, body.to_vec())
+}
+
+/// Sends a `POST` request with a JSON body to the router and returns the response with its body parsed as JSON.
+pub async fn post_json(router: &Router, uri: &str, body: serde_json::Value) -> (StatusCode, serde_json::Value) {
+    let request = Request::post(uri)
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    let response = router.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+/// Sends a `GET` request to the router and returns the response with its body parsed as JSON.
+pub async fn get_json(router: &Router, uri: &str) -> (StatusCode, serde_json::Value) {
+    let (status, _, body) = get(router, uri).await;
+    (status, serde_json::from_slice(&body).unwrap())
+}
@@ -0,0 +1,253 @@
+mod common;
+
+use axum::http::StatusCode;
+use axum::Router;
+use common::{get, get_json, message_data, post_json, time, MarketData};
+use serde_json::Value;
+
+/// Two days of `ethusdt` trades, with messages right at the end and start of the days.
+fn market_data() -> MarketData {
+    let market_data = MarketData::new();
+    market_data.write_day("trade", "ethusdt", "2019-04-05", &[
+        time(5, 10, 0, 0, 0),
+        time(5, 10, 0, 0, 500),
+        time(5, 12, 0, 0, 0),
+        time(5, 23, 59, 59, 999),
+    ]);
+    market_data.write_day("trade", "ethusdt", "2019-04-06", &[
+        time(6, 0, 0, 0, 0),
+        time(6, 0, 0, 1, 0),
+        time(6, 8, 0, 0, 0),
+    ]);
+    market_data
+}
+
+fn router(market_data: &MarketData) -> Router {
+    market_data.router(&[])
+}
+
+/// The `timestamp_millis` of the messages of a response.
+fn timestamps(messages: &Value) -> Vec<i64> {
+    messages
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|message| message["timestamp_millis"].as_i64().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn returns_the_messages_of_a_window() {
+    let market_data = market_data();
+    let (status, body) = get_json(
+        &router(&market_data),
+        "/api/v1/market-data/binance/spot/trade/ethusdt?from=2019-04-05T10:00:00Z&to=2019-04-05T12:00:00Z",
+    ).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(timestamps(&body["messages"]), vec![
+        time(5, 10, 0, 0, 0).timestamp_millis(),
+        time(5, 10, 0, 0, 500).timestamp_millis(),
+    ]);
+    assert_eq!(body["messages"][0]["symbol"], "ethusdt");
+    assert_eq!(body["messages"][0]["data"], message_data(&time(5, 10, 0, 0, 0)));
+    assert_eq!(body["file_count"], 1);
+}
+
+#[tokio::test]
+async fn includes_messages_at_to_with_inclusive_end() {
+    let market_data = market_data();
+    let (status, body) = get_json(
+        &router(&market_data),
+        "/api/v1/market-data/binance/spot/trade/ethusdt?from=2019-04-05T10:00:00Z&to=2019-04-05T12:00:00Z&inclusive_end=true",
+    ).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(timestamps(&body["messages"]).last(), Some(&time(5, 12, 0, 0, 0).timestamp_millis()));
+    assert_eq!(body["messages"].as_array().unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn returns_no_messages_for_an_empty_window_of_a_file() {
+    let market_data = market_data();
+    let (status, body) = get_json(
+        &router(&market_data),
+        "/api/v1/market-data/binance/spot/trade/ethusdt?from=2019-04-05T13:00:00Z&to=2019-04-05T14:00:00Z",
+    ).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["messages"], serde_json::json!([]));
+    assert_eq!(body["file_count"], 1);
+}
+
+#[tokio::test]
+async fn returns_the_messages_of_a_cross_day_query_in_order() {
+    let market_data = market_data();
+    let (status, body) = get_json(
+        &router(&market_data),
+        "/api/v1/market-data/binance/spot/trade/ethusdt?from=2019-04-05T23:00:00Z&to=2019-04-06T01:00:00Z",
+    ).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(timestamps(&body["messages"]), vec![
+        time(5, 23, 59, 59, 999).timestamp_millis(),
+        time(6, 0, 0, 0, 0).timestamp_millis(),
+        time(6, 0, 0, 1, 0).timestamp_millis(),
+    ]);
+    assert_eq!(body["file_count"], 2);
+}
+
+#[tokio::test]
+async fn reports_the_dates_without_files() {
+    let market_data = market_data();
+    let (status, body) = get_json(
+        &router(&market_data),
+        "/api/v1/market-data/binance/spot/trade/ethusdt?from=2019-04-06T06:00:00Z&to=2019-04-07T06:00:00Z",
+    ).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(timestamps(&body["messages"]), vec![time(6, 8, 0, 0, 0).timestamp_millis()]);
+    assert_eq!(body["missing_dates"], serde_json::json!(["2019-04-07"]));
+}
+
+#[tokio::test]
+async fn rejects_a_window_outside_the_files() {
+    let market_data = market_data();
+    let (status, body) = get_json(
+        &router(&market_data),
+        "/api/v1/market-data/binance/spot/trade/ethusdt?from=2019-05-01T00:00:00Z&to=2019-05-02T00:00:00Z",
+    ).await;
+
+    assert_eq!(status, StatusCode::RANGE_NOT_SATISFIABLE);
+    assert_eq!(body["error"]["code"], "RANGE_NOT_SATISFIABLE");
+}
+
+#[tokio::test]
+async fn returns_not_found_for_a_symbol_without_files() {
+    let market_data = market_data();
+    let (status, body) = get_json(
+        &router(&market_data),
+        "/api/v1/market-data/binance/spot/trade/btcusdt?from=2019-04-05T00:00:00Z&to=2019-04-06T00:00:00Z",
+    ).await;
+
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["error"]["code"], "DATA_NOT_FOUND");
+}
+
+#[tokio::test]
+async fn rejects_invalid_queries() {
+    let market_data = market_data();
+    let router = router(&market_data);
+    for uri in [
+        // Traversal out of the stream directory
+        "/api/v1/market-data/binance/spot/trade/..%2F..%2Fetc?from=2019-04-05T00:00:00Z&to=2019-04-06T00:00:00Z",
+        "/api/v1/market-data/binance/spot/..%2Ftrade/ethusdt?from=2019-04-05T00:00:00Z&to=2019-04-06T00:00:00Z",
+        "/api/v1/market-data/binance/spot/trade/ethusdt?from=2019-04-06T00:00:00Z&to=2019-04-05T00:00:00Z",
+        "/api/v1/market-data/binance/spot/trade/ethusdt?from=2019-04-05T00:00:00Z",
+    ] {
+        let (status, body) = get_json(&router, uri).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", uri);
+        assert_eq!(body["error"]["code"], "INVALID_PARAMETER", "{}", uri);
+    }
+}
+
+#[tokio::test]
+async fn streams_the_messages_of_a_cross_day_query_line_by_line() {
+    let market_data = market_data();
+    let (status, headers, body) = get(
+        &router(&market_data),
+        "/stream/v1/market-data/binance/spot/trade/ethusdt?from=2019-04-05T12:00:00Z&to=2019-04-06T08:00:00Z",
+    ).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers["x-accel-buffering"], "no");
+    let messages: Vec<Value> = String::from_utf8(body)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(timestamps(&Value::Array(messages)), vec![
+        time(5, 12, 0, 0, 0).timestamp_millis(),
+        time(5, 23, 59, 59, 999).timestamp_millis(),
+        time(6, 0, 0, 0, 0).timestamp_millis(),
+        time(6, 0, 0, 1, 0).timestamp_millis(),
+    ]);
+}
+
+#[tokio::test]
+async fn streams_a_cross_day_query_within_a_single_open_file() {
+    let market_data = market_data();
+    // Waiting for the file of the next day would only fail after the request timeout
+    let router = market_data.router(&["--max-open-files", "1", "--request-timeout-secs", "1"]);
+    let response = get(
+        &router,
+        "/stream/v1/market-data/binance/spot/trade/ethusdt?from=2019-04-05T12:00:00Z&to=2019-04-06T08:00:00Z",
+    );
+    let (status, _, body) = tokio::time::timeout(std::time::Duration::from_secs(10), response).await.unwrap();
+
+    assert_eq!(status, StatusCode::OK);
+    let messages: Vec<Value> = String::from_utf8(body)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(messages.len(), 4);
+    assert!(messages.iter().all(|message| message.get("error").is_none()));
+}
+
+#[tokio::test]
+async fn streams_nothing_for_an_empty_window() {
+    let market_data = market_data();
+    let (status, _, body) = get(
+        &router(&market_data),
+        "/stream/v1/market-data/binance/spot/trade/ethusdt?from=2019-04-05T13:00:00Z&to=2019-04-05T14:00:00Z",
+    ).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.is_empty());
+}
+
+#[tokio::test]
+async fn fails_the_sub_queries_of_a_batch_beyond_the_maximum_messages() {
+    let market_data = market_data();
+    let router = market_data.router(&["--max-response-messages", "3"]);
+    let sub_query = |from: &str, to: &str| serde_json::json!({
+        "exchange": "binance", "market_type": "spot", "stream": "trade", "symbol": "ethusdt", "from": from, "to": to,
+    });
+    let (status, body) = post_json(&router, "/api/v1/market-data/query", serde_json::json!([
+        sub_query("2019-04-05T10:00:00Z", "2019-04-05T11:00:00Z"),
+        sub_query("2019-04-06T00:00:00Z", "2019-04-06T01:00:00Z"),
+    ])).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["results"]["0"]["messages"].as_array().unwrap().len(), 2);
+    // The limit is shared by all sub-queries of the batch
+    let error = body["results"]["1"]["error"].as_str().unwrap();
+    assert!(error.contains("maximum of 3 messages"), "{}", error);
+    assert!(error.contains("streaming endpoint"), "{}", error);
+}
+
+#[tokio::test]
+async fn counts_the_messages_of_a_cross_day_query() {
+    let market_data = market_data();
+    let (status, body) = get_json(
+        &router(&market_data),
+        "/api/v1/market-data/binance/spot/trade/ethusdt/count?from=2019-04-05T10:00:00Z&to=2019-04-06T01:00:00Z",
+    ).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["count"], 6);
+}
+
+#[tokio::test]
+async fn returns_the_time_range_of_all_files() {
+    let market_data = market_data();
+    let (status, body) = get_json(&router(&market_data), "/api/v1/market-data/binance/spot/trade/ethusdt/range").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["first_date"], "2019-04-05");
+    assert_eq!(body["last_date"], "2019-04-06");
+    assert_eq!(body["file_count"], 2);
+    assert_eq!(body["min_timestamp_millis"], time(5, 10, 0, 0, 0).timestamp_millis());
+    assert_eq!(body["max_timestamp_millis"], time(6, 8, 0, 0, 0).timestamp_millis());
+}